@@ -0,0 +1,306 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    Action, Application, BasicRenderer, DebugRasterizer, DebugStats, Event, Handles, Pixel,
+    ShouldRender, Size, Snapshot, StopReason, Surface, Update,
+};
+
+/// Drives an [`Application`] without a terminal, for testing
+///
+/// Time only moves when [`Harness::advance`] is called, so updates are deterministic.
+/// The last rendered frame can be inspected with [`Harness::snapshot`]
+pub struct Harness<A> {
+    app: A,
+    handles: Handles,
+    renderer: BasicRenderer,
+    rasterizer: DebugRasterizer,
+    frame: Surface<Pixel>,
+    last_frame: Instant,
+    now: Instant,
+    pending_resize: Option<(Size, Instant)>,
+    should_redraw: bool,
+    quit: bool,
+}
+
+impl<A: Application> Harness<A> {
+    /// Starts the application and renders the first frame
+    pub fn new(mut app: A, size: Size) -> Self {
+        let handles = Handles::default();
+        let mut renderer = BasicRenderer::new(size);
+        app.connect(handles.clone());
+        app.start(size, &mut renderer);
+
+        let now = Instant::now();
+        let mut this = Self {
+            app,
+            handles,
+            renderer,
+            rasterizer: DebugRasterizer::new(),
+            frame: Surface::new(size, Pixel::empty()),
+            last_frame: now,
+            now,
+            pending_resize: None,
+            should_redraw: false,
+            quit: false,
+        };
+        this.render();
+        this
+    }
+
+    /// Delivers an event, the way [`run`](crate::run) would
+    ///
+    /// Resizes are debounced against the harness' clock: once the size has held for
+    /// [`Application::resize_debounce`] the final size is delivered on the next [`Harness::advance`]
+    pub fn event(&mut self, event: Event) -> &mut Self {
+        if self.quit {
+            return self;
+        }
+
+        match &event {
+            Event::Quit => return self.stop(StopReason::Closed),
+            Event::Resize { size } => {
+                self.pending_resize = Some((*size, self.now));
+                return self;
+            }
+            Event::FocusGained | Event::FocusLost => {
+                self.app.focus(event == Event::FocusGained);
+                self.should_redraw = true;
+            }
+            _ => {}
+        }
+
+        if let Action::Quit = self.app.event(event) {
+            self.stop(StopReason::Quit);
+        }
+        self
+    }
+
+    pub fn events(&mut self, events: impl IntoIterator<Item = Event>) -> &mut Self {
+        for event in events {
+            self.event(event);
+        }
+        self
+    }
+
+    /// Moves the clock forward and runs a frame, rendering if the application asks for it
+    pub fn advance(&mut self, dt: Duration) -> &mut Self {
+        if self.quit {
+            return self;
+        }
+
+        self.last_frame = self.now;
+        self.now += dt;
+        let update = Update {
+            last_frame: self.last_frame,
+            current: self.now,
+            dt: dt.as_secs_f32(),
+            absolute_dt: dt.as_secs_f32(),
+        };
+
+        if let Some((size, last)) = self.pending_resize
+            && self.now - last >= self.app.resize_debounce()
+        {
+            self.pending_resize = None;
+            self.renderer.resize(size, self.app.resize_mode());
+            self.should_redraw = true;
+            if let Action::Quit = self.app.event(Event::Resize { size }) {
+                return self.stop(StopReason::Quit);
+            }
+        }
+
+        let should_redraw = std::mem::take(&mut self.should_redraw);
+        // like `run`, nothing is drawn while a resize is pending
+        if (self.app.update(update) == ShouldRender::Yes || should_redraw)
+            && self.pending_resize.is_none()
+        {
+            self.render();
+        }
+
+        if self.app.should_quit() {
+            self.stop(StopReason::Quit);
+        }
+        self
+    }
+
+    /// Renders a frame regardless of what the application wants
+    pub fn render(&mut self) -> &mut Self {
+        self.app.render(&mut self.renderer);
+        self.frame.clone_from(self.renderer.surface());
+        let Ok(..) = self.renderer.render(&mut self.rasterizer);
+        self
+    }
+
+    pub const fn now(&self) -> Instant {
+        self.now
+    }
+
+    pub const fn is_quit(&self) -> bool {
+        self.quit
+    }
+
+    pub const fn app(&self) -> &A {
+        &self.app
+    }
+
+    pub const fn app_mut(&mut self) -> &mut A {
+        &mut self.app
+    }
+
+    /// The handles given to the application, e.g. to look at its [`HoverRegions`](crate::HoverRegions)
+    pub const fn handles(&self) -> &Handles {
+        &self.handles
+    }
+
+    /// The last rendered frame
+    pub const fn frame(&self) -> &Surface<Pixel> {
+        &self.frame
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot::new(&self.frame)
+    }
+
+    pub fn snapshot_with_styles(&self) -> Snapshot {
+        Snapshot::with_styles(&self.frame)
+    }
+
+    /// The rasterizer operations of the last rendered frame
+    pub fn ops(&self) -> String {
+        self.rasterizer.to_string()
+    }
+
+    /// How much the last rendered frame wrote, e.g. to check that an unchanged frame writes nothing
+    pub const fn stats(&self) -> DebugStats {
+        self.rasterizer.stats()
+    }
+
+    fn stop(&mut self, reason: StopReason<'_>) -> &mut Self {
+        if !std::mem::replace(&mut self.quit, true) {
+            self.app.stop(reason);
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BlendMode, Key, KeyModifiers, Renderer};
+
+    #[derive(Default)]
+    struct Counter {
+        events: Vec<Event>,
+        presses: usize,
+        dirty: bool,
+        renders: usize,
+        stopped: Vec<String>,
+    }
+
+    impl Application for Counter {
+        fn stop(&mut self, reason: StopReason<'_>) {
+            self.stopped.push(format!("{reason:?}"));
+        }
+
+        fn update(&mut self, _: Update) -> ShouldRender {
+            match std::mem::take(&mut self.dirty) {
+                true => ShouldRender::Yes,
+                false => ShouldRender::No,
+            }
+        }
+
+        fn event(&mut self, event: Event) -> Action {
+            self.events.push(event.clone());
+            match event {
+                Event::KeyPress {
+                    key: Key::Char('q'),
+                    ..
+                } => Action::Quit,
+                Event::KeyPress { .. } => {
+                    self.presses += 1;
+                    self.dirty = true;
+                    Action::Continue
+                }
+                _ => Action::Continue,
+            }
+        }
+
+        fn render(&mut self, renderer: &mut impl Renderer) {
+            self.renders += 1;
+            renderer.clear();
+            renderer.draw(format!("presses {}", self.presses), BlendMode::Replace);
+        }
+    }
+
+    fn press(ch: char) -> Event {
+        Event::KeyPress {
+            key: Key::Char(ch),
+            modifiers: KeyModifiers::NONE,
+        }
+    }
+
+    const FRAME: Duration = Duration::from_millis(16);
+
+    #[test]
+    fn renders_when_asked() {
+        let mut harness = Harness::new(Counter::default(), Size::new(12, 1));
+        harness.snapshot().assert_eq("presses 0\n");
+        assert_eq!(harness.app().renders, 1);
+
+        harness.advance(FRAME);
+        assert_eq!(harness.app().renders, 1);
+
+        let start = harness.now();
+        harness.event(press('a')).event(press('b')).advance(FRAME);
+        harness.snapshot().assert_eq("presses 2\n");
+        assert_eq!(harness.app().renders, 2);
+        assert_eq!(harness.now() - start, FRAME);
+    }
+
+    #[test]
+    fn debounces_resizes() {
+        let mut harness = Harness::new(Counter::default(), Size::new(10, 1));
+
+        harness
+            .event(Event::Resize {
+                size: Size::new(10, 2),
+            })
+            .advance(Duration::from_millis(20))
+            .event(Event::Resize {
+                size: Size::new(14, 3),
+            })
+            .advance(Duration::from_millis(40));
+        // the last resize was only 40ms ago
+        assert_eq!(harness.app().events, []);
+        assert_eq!(harness.frame().size(), Size::new(10, 1));
+
+        // nothing is drawn while the resize is pending
+        harness.event(press('a')).advance(Duration::from_millis(5));
+        assert_eq!(harness.app().renders, 1);
+
+        harness.advance(FRAME);
+        let resize = Event::Resize {
+            size: Size::new(14, 3),
+        };
+        assert_eq!(harness.app().events, [press('a'), resize]);
+        assert_eq!(harness.frame().size(), Size::new(14, 3));
+        harness.snapshot().assert_eq("presses 1\n\n\n");
+        assert_eq!(harness.app().renders, 2);
+    }
+
+    #[test]
+    fn quits() {
+        let mut harness = Harness::new(Counter::default(), Size::new(12, 1));
+        harness.event(press('q'));
+        assert!(harness.is_quit());
+
+        // nothing reaches the application once it has stopped
+        harness
+            .event(press('a'))
+            .event(Event::Quit)
+            .advance(FRAME)
+            .advance(FRAME);
+        assert_eq!(harness.app().events, [press('q')]);
+        assert_eq!(harness.app().stopped, ["Quit"]);
+        harness.snapshot().assert_eq("presses 0\n");
+    }
+}
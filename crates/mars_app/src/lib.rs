@@ -5,7 +5,13 @@ pub use mars_surface::*;
 #[doc(inline)]
 pub use mars_terminal::*;
 
-use std::time::{Duration, Instant};
+use std::{
+    io::Write as _,
+    time::{Duration, Instant},
+};
+
+mod harness;
+pub use harness::Harness;
 
 #[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
 #[non_exhaustive]
@@ -15,13 +21,41 @@ pub enum Action {
     Quit,
 }
 
+/// Why an [`Application`] is being stopped
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum StopReason<'a> {
+    /// The application asked to, with [`Action::Quit`] or [`Application::should_quit`]
+    Quit,
+    /// The backend sent [`Event::Quit`], e.g. its input was closed
+    Closed,
+    /// Talking to the terminal failed, [`run`] returns this error
+    Error(&'a std::io::Error),
+}
+
+/// Handles to the terminal's shared state, these can be kept by the application
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct Handles {
+    pub clipboard: Clipboard,
+    pub hover_regions: HoverRegions,
+}
+
 pub trait Application {
+    /// Called before [`Application::start`] with handles to the terminal's shared state
+    fn connect(&mut self, handles: Handles) {
+        _ = handles
+    }
+
     fn start(&mut self, size: Size, renderer: &mut impl RendererSetup) {
         _ = size;
         _ = renderer
     }
 
-    fn stop(&mut self) {}
+    /// Called once before the application exits, with why it's stopping
+    fn stop(&mut self, reason: StopReason<'_>) {
+        _ = reason
+    }
 
     fn should_quit(&self) -> bool {
         false
@@ -37,6 +71,32 @@ pub trait Application {
         Action::Continue
     }
 
+    /// Called when the terminal window gains or loses focus
+    ///
+    /// This is only called if the [`Terminal`] was created with [`Config::focus_reporting`]
+    fn focus(&mut self, focused: bool) {
+        _ = focused
+    }
+
+    /// The frame rate to use while the terminal window isn't focused
+    ///
+    /// By default the frame rate isn't changed
+    fn unfocused_fps(&self) -> Option<f32> {
+        None
+    }
+
+    /// How long the terminal has to stay the same size before a resize is delivered
+    ///
+    /// Resizes within this window are coalesced, only the final size is passed on as an [`Event::Resize`]
+    fn resize_debounce(&self) -> Duration {
+        Duration::from_millis(50)
+    }
+
+    /// What happens to the renderer's contents when the terminal is resized
+    fn resize_mode(&self) -> ResizeMode {
+        ResizeMode::Discard
+    }
+
     fn render(&mut self, renderer: &mut impl Renderer);
 }
 
@@ -55,7 +115,7 @@ pub trait Runner: Application + Sized {
         self.start(size, &mut surface);
         self.render(&mut surface);
         let Ok(..) = surface.render(&mut dr);
-        self.stop();
+        self.stop(StopReason::Quit);
 
         dr.to_string()
     }
@@ -78,27 +138,20 @@ pub struct Update {
     pub absolute_dt: f32,
 }
 
-pub fn run(fps: f32, mut term: Terminal, mut app: impl Application) -> std::io::Result<()> {
+pub fn run(fps: f32, mut term: impl Backend, mut app: impl Application) -> std::io::Result<()> {
     assert!(fps >= 1.0, "fps must be atleast 1.0");
 
     let mut surface = BasicRenderer::new(term.size());
-    let mut br = BufferedRasterizer::new();
 
+    app.connect(Handles {
+        clipboard: term.clipboard(),
+        hover_regions: term.hover_regions(),
+    });
     app.start(term.size(), &mut surface);
 
     // first render to clear the bg
-    let (fg, bg) = surface.default_colors();
-    surface.fill(
-        Position::ZERO,
-        term.size(),
-        Pixel::empty().fg(fg).bg(bg),
-        BlendMode::Replace,
-    );
-    let Ok(..) = surface.render(&mut br);
-    let Ok(..) = br.clear_screen(bg, term.size());
-    if let err @ Err(..) = br.copy_to(&mut term) {
-        app.stop();
-        return err;
+    if let Err(err) = clear(&mut term, &mut surface, ResizeMode::Discard) {
+        return fail(&mut app, err);
     }
 
     let mut last_frame = Instant::now();
@@ -106,9 +159,15 @@ pub fn run(fps: f32, mut term: Terminal, mut app: impl Application) -> std::io::
     let mut lag = Duration::ZERO;
     let mut absolute_dt = 1.0;
 
-    let target = 1.0 / fps;
+    let mut focused = true;
+    let mut pending_resize = None;
 
     while !app.should_quit() {
+        let target = match app.unfocused_fps() {
+            Some(fps) if !focused => 1.0 / fps.max(1.0),
+            _ => 1.0 / fps,
+        };
+
         let update = Update {
             last_frame,
             current: now,
@@ -119,37 +178,76 @@ pub fn run(fps: f32, mut term: Terminal, mut app: impl Application) -> std::io::
         let mut should_redraw = false;
         while let Some(ev) = term.try_read_event() {
             if ev.is_quit() {
-                app.stop();
+                app.stop(StopReason::Closed);
                 return Ok(());
             }
 
-            // TODO debounce these resizes
-            if let Event::Resize { size } = &ev {
-                surface.resize(*size, ResizeMode::Discard);
-                should_redraw ^= true;
+            if let Event::Resize { .. } = &ev {
+                pending_resize = Some(Instant::now());
+                continue;
+            }
+
+            // the screen is in an unknown state after we're resumed, so start over
+            if let Event::Suspend | Event::Resume = &ev {
+                let result = match ev {
+                    Event::Suspend => term.suspend(),
+                    _ => term.resume(),
+                };
+                if let Err(err) =
+                    result.and_then(|_| clear(&mut term, &mut surface, ResizeMode::Discard))
+                {
+                    return fail(&mut app, err);
+                }
+                should_redraw = true;
+                continue;
+            }
+
+            if let Event::FocusGained | Event::FocusLost = &ev {
+                focused = ev == Event::FocusGained;
+                app.focus(focused);
+                should_redraw = true;
             }
 
             if let Action::Quit = app.event(ev) {
-                app.stop();
+                app.stop(StopReason::Quit);
                 return Ok(());
             }
         }
 
-        should_redraw ^= app.update(update) == ShouldRender::Yes;
+        if let Some(last) = pending_resize
+            && last.elapsed() >= app.resize_debounce()
+        {
+            pending_resize = None;
+            if let Err(err) = clear(&mut term, &mut surface, app.resize_mode()) {
+                return fail(&mut app, err);
+            }
 
-        if should_redraw {
-            // let (_, bg) = surface.default_colors();
-            // let Ok(..) = br.clear_screen(bg, surface.size());
+            if let Action::Quit = app.event(Event::Resize { size: term.size() }) {
+                app.stop(StopReason::Quit);
+                return Ok(());
+            }
+            should_redraw = true;
+        }
 
-            app.render(&mut surface);
-            let Ok(..) = surface.render(&mut br);
+        should_redraw |= app.update(update) == ShouldRender::Yes;
+
+        // the terminal is still being resized, drawing now would just flicker
+        if should_redraw && pending_resize.is_none() {
+            // an inline viewport moves when the terminal is resized
+            let origin = term.origin();
 
-            if let err @ Err(_) = br.copy_to(&mut term) {
-                app.stop();
-                return err;
+            app.render(&mut surface);
+            if let Err(err) =
+                surface.render(StreamingRasterizer::new(&mut term).with_origin(origin))
+            {
+                return fail(&mut app, err);
             }
         }
 
+        if let Err(err) = term.flush_clipboard() {
+            return fail(&mut app, err);
+        }
+
         let current = Instant::now();
         absolute_dt = current.duration_since(now).as_secs_f32();
 
@@ -163,6 +261,131 @@ pub fn run(fps: f32, mut term: Terminal, mut app: impl Application) -> std::io::
         last_frame = std::mem::replace(&mut now, next)
     }
 
-    app.stop();
+    app.stop(StopReason::Quit);
     Ok(())
 }
+
+/// Stops the application because of an error, which is then returned from [`run`]
+fn fail(app: &mut impl Application, err: std::io::Error) -> std::io::Result<()> {
+    app.stop(StopReason::Error(&err));
+    Err(err)
+}
+
+/// Fits the renderer to the terminal and clears the screen
+fn clear(
+    term: &mut impl Backend,
+    surface: &mut BasicRenderer,
+    mode: ResizeMode,
+) -> std::io::Result<()> {
+    let (size, origin) = (term.size(), term.origin());
+    surface.resize(size, mode);
+
+    let (_, bg) = surface.default_colors();
+    let mut out = StreamingRasterizer::new(term).with_origin(origin);
+    out.clear_screen(bg, size)?;
+    out.get_mut().flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    #[derive(Default)]
+    struct Typist {
+        typed: String,
+        log: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl Application for Typist {
+        fn start(&mut self, size: Size, _: &mut impl RendererSetup) {
+            self.log.borrow_mut().push(format!("start {size:?}"));
+        }
+
+        fn stop(&mut self, reason: StopReason<'_>) {
+            self.log.borrow_mut().push(format!("stop {reason:?}"));
+        }
+
+        fn event(&mut self, event: Event) -> Action {
+            let Event::KeyPress {
+                key: Key::Char(ch), ..
+            } = event
+            else {
+                return Action::Continue;
+            };
+            self.log.borrow_mut().push(format!("key {ch}"));
+            if ch == 'q' {
+                return Action::Quit;
+            }
+            self.typed.push(ch);
+            Action::Continue
+        }
+
+        fn render(&mut self, renderer: &mut impl Renderer) {
+            renderer.clear();
+            renderer.draw(format!("typed {}", self.typed), BlendMode::Replace);
+        }
+    }
+
+    fn press(ch: char) -> Event {
+        Event::KeyPress {
+            key: Key::Char(ch),
+            modifiers: KeyModifiers::NONE,
+        }
+    }
+
+    #[test]
+    fn run_mock_terminal() {
+        let mut term = MockTerminal::new(Size::new(10, 1)).events([press('a'), press('b')]);
+        term.push_frame();
+        term.push_event(press('q'));
+        term.push_event(press('c'));
+
+        let app = Typist::default();
+        let log = app.log.clone();
+        run(1000.0, &mut term, app).unwrap();
+
+        let output = String::from_utf8_lossy(term.output());
+        assert!(output.contains("typed ab"), "{output:?}");
+        assert!(!output.contains("typed abc"), "{output:?}");
+        assert_eq!(
+            *log.borrow(),
+            [
+                format!("start {:?}", Size::new(10, 1)),
+                "key a".into(),
+                "key b".into(),
+                "key q".into(),
+                "stop Quit".into(),
+            ]
+        );
+    }
+
+    #[test]
+    fn run_until_the_script_ends() {
+        let mut term = MockTerminal::new(Size::new(10, 1)).events([press('x')]);
+        let app = Typist::default();
+        let log = app.log.clone();
+        run(1000.0, &mut term, app).unwrap();
+
+        // the mock lets the last event be drawn before it quits
+        let output = String::from_utf8_lossy(term.output());
+        assert!(output.contains("typed x"), "{output:?}");
+        assert_eq!(log.borrow().last().unwrap(), "stop Closed");
+        assert_eq!(log.borrow().len(), 3);
+    }
+
+    #[test]
+    fn run_stops_with_the_error() {
+        let mut term = MockTerminal::new(Size::new(10, 1));
+        term.close();
+        let app = Typist::default();
+        let log = app.log.clone();
+
+        let err = run(1000.0, &mut term, app).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::BrokenPipe);
+        assert_eq!(
+            log.borrow().last().unwrap(),
+            &format!("stop Error({err:?})")
+        );
+    }
+}
@@ -0,0 +1,260 @@
+//! Compares how many bytes a frame takes with the [`BufferedRasterizer`] against the encoding it used to have
+//!
+//! `cargo run -p mars_surface --example encoding_bytes`
+
+use std::{io::Write as _, ops::Range};
+
+use mars_math::{Position, Size};
+use mars_surface::{
+    Attributes, BasicRenderer, BlendMode, BufferedRasterizer, Color, CursorShape, IndexedColor,
+    Pixel, Rasterizer, Rgba, StreamingRasterizer,
+};
+
+const SIZE: Size = Size::new(80, 24);
+
+type Scene = fn(&mut BasicRenderer);
+
+fn main() {
+    let scenes: [(&str, Scene); 5] = [
+        ("blank", |_| {}),
+        ("text", text),
+        ("ui", ui),
+        ("sparse", sparse),
+        ("gradient", gradient),
+    ];
+
+    println!(
+        "{:<10} {:>8} {:>8} {:>8} {:>8}",
+        "scene", "before", "after", "no REP", "saved"
+    );
+    for (name, scene) in scenes {
+        let before = frame_len(scene, Legacy::default());
+        let after = frame_len(scene, BufferedRasterizer::new());
+        let no_rep = frame_len(scene, StreamingRasterizer::new(Vec::new()).repeat(false));
+        println!(
+            "{name:<10} {before:>8} {after:>8} {no_rep:>8} {:>7.1}%",
+            100.0 - after as f32 / before as f32 * 100.0
+        );
+    }
+}
+
+fn frame_len<R: Rasterizer + Len>(scene: Scene, mut rasterizer: R) -> usize {
+    let mut renderer = BasicRenderer::new(SIZE);
+    renderer.set_default_bg(Rgba::hex("#112"));
+    scene(&mut renderer);
+    let Ok(..) = renderer.render(&mut rasterizer) else {
+        unreachable!("writing to memory")
+    };
+    rasterizer.len()
+}
+
+fn text(renderer: &mut BasicRenderer) {
+    const LOREM: &str = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor \
+        incididunt ut labore et dolore magna aliqua. Ut enim ad minim veniam, quis nostrud exercitation \
+        ullamco laboris nisi ut aliquip ex ea commodo consequat.";
+
+    let mut words = LOREM.split(' ').cycle();
+    for y in 1..SIZE.height as i32 - 1 {
+        let mut x = 2;
+        loop {
+            let word = words.next().unwrap_or_default();
+            if x + word.len() as i32 >= SIZE.width as i32 - 2 {
+                break;
+            }
+            for (i, ch) in word.chars().enumerate() {
+                put(renderer, x + i as i32, y, Pixel::new(ch));
+            }
+            x += word.len() as i32 + 1;
+        }
+    }
+}
+
+fn ui(renderer: &mut BasicRenderer) {
+    let (w, h) = (SIZE.width as i32, SIZE.height as i32);
+    for x in 0..w {
+        put(renderer, x, 0, Pixel::new('─').fg(IndexedColor(8)));
+        put(renderer, x, h - 2, Pixel::new('─').fg(IndexedColor(8)));
+        let status = Pixel::new(' ').bg(Rgba::hex("#335"));
+        put(renderer, x, h - 1, status);
+    }
+    for y in 1..h - 2 {
+        put(renderer, 0, y, Pixel::new('│').fg(IndexedColor(8)));
+        put(renderer, w - 1, y, Pixel::new('│').fg(IndexedColor(8)));
+    }
+    for (i, ch) in " mars ".chars().enumerate() {
+        put(
+            renderer,
+            3 + i as i32,
+            0,
+            Pixel::new(ch).fg(Rgba::hex("#fa0")),
+        );
+    }
+    for (i, ch) in "NORMAL  main.rs  12:40".chars().enumerate() {
+        let pixel = Pixel::new(ch).fg(Rgba::hex("#eee")).bg(Rgba::hex("#335"));
+        put(renderer, 1 + i as i32, h - 1, pixel);
+    }
+    for y in 2..h - 3 {
+        let line = format!("{y:>3}  let value = compute({y}, {});", y * 7);
+        for (i, ch) in line.chars().enumerate() {
+            let fg = match ch {
+                '0'..='9' => Color::from(Rgba::hex("#9cf")),
+                '(' | ')' | ',' | ';' => Color::from(IndexedColor(7)),
+                _ => Color::Default,
+            };
+            put(renderer, 2 + i as i32, y, Pixel::new(ch).fg(fg));
+        }
+    }
+}
+
+fn sparse(renderer: &mut BasicRenderer) {
+    for i in 0..12 {
+        let (x, y) = ((i * 37) % SIZE.width as i32, (i * 11) % SIZE.height as i32);
+        put(renderer, x, y, Pixel::new('*').fg(Rgba::hex("#ff0")));
+    }
+}
+
+fn gradient(renderer: &mut BasicRenderer) {
+    for y in 0..SIZE.height as i32 {
+        for x in 0..SIZE.width as i32 {
+            let bg = Rgba::new((x * 3) as u8, (y * 10) as u8, 128, 255);
+            put(renderer, x, y, Pixel::new(' ').bg(bg));
+        }
+    }
+}
+
+fn put(renderer: &mut BasicRenderer, x: i32, y: i32, pixel: Pixel) {
+    renderer.put(Position::new(x, y), pixel, BlendMode::Replace);
+}
+
+trait Len {
+    fn len(&self) -> usize;
+}
+
+impl Len for BufferedRasterizer {
+    fn len(&self) -> usize {
+        Self::len(self)
+    }
+}
+
+impl Len for StreamingRasterizer<Vec<u8>> {
+    fn len(&self) -> usize {
+        self.get_ref().len()
+    }
+}
+
+impl Len for Legacy {
+    fn len(&self) -> usize {
+        self.out.len()
+    }
+}
+
+/// The encoding the `BufferedRasterizer` had before, one absolute move or `SGR` per change
+#[derive(Default)]
+struct Legacy {
+    out: Vec<u8>,
+}
+
+impl Rasterizer for Legacy {
+    type Error = std::io::Error;
+
+    fn begin(&mut self) -> Result<(), Self::Error> {
+        self.out.write_all(b"\x1b[?2026h")
+    }
+
+    fn end(&mut self) -> Result<(), Self::Error> {
+        self.out.write_all(b"\x1b[?2026l")
+    }
+
+    fn clear(&mut self, pos: Position, size: Size) -> Result<(), Self::Error> {
+        let (x, y) = (pos.x + 1, pos.y + 1);
+        let (w, h) = (size.width, size.height);
+        write!(&mut self.out, "\x1b[{y};{x};{h};{w}$z")
+    }
+
+    fn clear_screen(&mut self, bg: Color, size: Size) -> Result<(), Self::Error> {
+        for y in 0..size.height {
+            self.move_to(Position::new(0, y as _))?;
+            self.set_bg(bg)?;
+            write!(&mut self.out, "\x1b[{}@", size.width)?
+        }
+        Ok(())
+    }
+
+    fn move_to(&mut self, pos: Position) -> Result<(), Self::Error> {
+        write!(&mut self.out, "\x1b[{};{};H", pos.y + 1, pos.x + 1)
+    }
+
+    fn scroll(&mut self, rows: Range<u32>, lines: i32) -> Result<bool, Self::Error> {
+        let (top, bottom) = (rows.start + 1, rows.end);
+        if lines < 0 {
+            write!(&mut self.out, "\x1b[{top};{bottom}r\x1b[{}T\x1b[r", -lines)?
+        } else {
+            write!(&mut self.out, "\x1b[{top};{bottom}r\x1b[{lines}S\x1b[r")?
+        }
+        Ok(true)
+    }
+
+    fn default_fg(&mut self, color: Color) -> Result<(), Self::Error> {
+        self.set_fg(color)
+    }
+
+    fn default_bg(&mut self, color: Color) -> Result<(), Self::Error> {
+        self.set_bg(color)
+    }
+
+    fn set_fg(&mut self, color: Color) -> Result<(), Self::Error> {
+        match color {
+            Color::Named(IndexedColor(color)) => write!(&mut self.out, "\x1b[38;5;{color}m"),
+            Color::Rgba(Rgba(r, g, b, _)) => write!(&mut self.out, "\x1b[38;2;{r};{g};{b}m"),
+            Color::Default => self.reset_fg(),
+        }
+    }
+
+    fn set_bg(&mut self, color: Color) -> Result<(), Self::Error> {
+        match color {
+            Color::Named(IndexedColor(color)) => write!(&mut self.out, "\x1b[48;5;{color}m"),
+            Color::Rgba(Rgba(r, g, b, _)) => write!(&mut self.out, "\x1b[48;2;{r};{g};{b}m"),
+            Color::Default => self.reset_bg(),
+        }
+    }
+
+    fn set_attribute(&mut self, attribute: Attributes) -> Result<(), Self::Error> {
+        let mut seen = false;
+        for i in attribute.as_indexed_bytes() {
+            seen = true;
+            write!(&mut self.out, "\x1b[{i}m")?;
+        }
+        if !seen {
+            self.out.write_all(b"\x1b[0m")?;
+        }
+        Ok(())
+    }
+
+    fn reset_fg(&mut self) -> Result<(), Self::Error> {
+        self.out.write_all(b"\x1b[39m")
+    }
+
+    fn reset_bg(&mut self) -> Result<(), Self::Error> {
+        self.out.write_all(b"\x1b[49m")
+    }
+
+    fn reset_attribute(&mut self) -> Result<(), Self::Error> {
+        self.out.write_all(b"\x1b[59m")
+    }
+
+    fn write(&mut self, data: &str) -> Result<(), Self::Error> {
+        write!(&mut self.out, "{data}")
+    }
+
+    fn show_cursor(&mut self) -> Result<(), Self::Error> {
+        self.out.write_all(b"\x1b[?25h")
+    }
+
+    fn hide_cursor(&mut self) -> Result<(), Self::Error> {
+        self.out.write_all(b"\x1b[?25l")
+    }
+
+    fn set_cursor_shape(&mut self, shape: CursorShape, blinking: bool) -> Result<(), Self::Error> {
+        write!(&mut self.out, "\x1b[{} q", shape.style(blinking))
+    }
+}
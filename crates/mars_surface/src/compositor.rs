@@ -0,0 +1,583 @@
+use mars_math::{Position, Size};
+
+use crate::{BlendMode, Color, Pixel, Placer, Renderer, Rgba, Surface, pixel::PixelData};
+
+/// An off-screen [`Surface<Pixel>`] that a [`Compositor`] places over the frame
+///
+/// It starts out fully transparent, and with [`BlendMode::Blend`] its cells are mixed over what's under them:
+/// - the background is mixed by its alpha, times the layer's opacity
+/// - blank cells let the text under them show through, tinted by that background
+/// - text is faded by the layer's opacity
+///
+/// With [`BlendMode::Replace`] its cells are copied over as they are.
+///
+/// Only the cells drawn into since the layer was created or [cleared](Layer::clear) are flattened, these are
+/// its surface's [damage](Surface::damage), so clearing that damage hides what was drawn
+#[derive(Clone, Debug)]
+pub struct Layer {
+    surface: Surface<Pixel>,
+    pos: Position,
+    z_index: i32,
+    visible: bool,
+    opacity: f32,
+    blend: BlendMode,
+}
+
+impl Layer {
+    pub fn new(size: Size) -> Self {
+        let mut surface = Surface::new(size, transparent());
+        surface.clear_damage();
+        Self {
+            surface,
+            pos: Position::ZERO,
+            z_index: 0,
+            visible: true,
+            opacity: 1.0,
+            blend: BlendMode::Blend,
+        }
+    }
+
+    pub const fn with_position(mut self, pos: Position) -> Self {
+        self.pos = pos;
+        self
+    }
+
+    /// Layers with a higher z-index are drawn over those with a lower one, ties go by insertion order
+    pub const fn with_z_index(mut self, z_index: i32) -> Self {
+        self.z_index = z_index;
+        self
+    }
+
+    pub const fn with_opacity(mut self, opacity: f32) -> Self {
+        self.opacity = opacity.clamp(0.0, 1.0);
+        self
+    }
+
+    pub const fn with_blend(mut self, blend: BlendMode) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    pub const fn position(&self) -> Position {
+        self.pos
+    }
+
+    pub const fn set_position(&mut self, pos: Position) {
+        self.pos = pos;
+    }
+
+    pub const fn z_index(&self) -> i32 {
+        self.z_index
+    }
+
+    pub const fn set_z_index(&mut self, z_index: i32) {
+        self.z_index = z_index;
+    }
+
+    pub const fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub const fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    pub const fn opacity(&self) -> f32 {
+        self.opacity
+    }
+
+    pub const fn set_opacity(&mut self, opacity: f32) {
+        self.opacity = opacity.clamp(0.0, 1.0);
+    }
+
+    pub const fn blend(&self) -> BlendMode {
+        self.blend
+    }
+
+    pub const fn set_blend(&mut self, blend: BlendMode) {
+        self.blend = blend;
+    }
+
+    pub const fn surface(&self) -> &Surface<Pixel> {
+        &self.surface
+    }
+
+    pub const fn surface_mut(&mut self) -> &mut Surface<Pixel> {
+        &mut self.surface
+    }
+
+    /// Makes every cell transparent again
+    pub fn clear(&mut self) {
+        self.surface.fill(transparent());
+        self.surface.clear_damage();
+    }
+
+    /// What the cell `over` from this layer turns `under` into, if it changes it at all
+    fn composite(&self, under: &Pixel, over: &Pixel, (fg, bg): (Color, Color)) -> Option<Pixel> {
+        let alpha = self.opacity
+            * match over.background {
+                Color::Rgba(color) => color.alpha() as f32 / 255.0,
+                _ => 1.0,
+            };
+        let blank = matches!(over.data, PixelData::Char(' '));
+        if alpha <= 0.0 && blank {
+            return None;
+        }
+        let background = mix((under.background, bg), (over.background, bg), alpha);
+
+        if alpha < 1.0 && blank {
+            return Some(Pixel {
+                data: under.data.clone(),
+                foreground: mix((under.foreground, fg), (over.background, bg), alpha),
+                background,
+                attributes: under.attributes,
+            });
+        }
+
+        Some(Pixel {
+            data: over.data.clone(),
+            foreground: mix((background, bg), (over.foreground, fg), self.opacity),
+            background,
+            attributes: over.attributes,
+        })
+    }
+}
+
+impl Placer for Layer {
+    fn put(&mut self, pos: Position, pixel: Pixel, _blend: BlendMode) {
+        self.surface.set(pos, pixel);
+    }
+
+    fn size(&self) -> Size {
+        self.surface.size()
+    }
+}
+
+/// Named [`Layer`]s, flattened over a renderer in z-order
+///
+/// The layers keep what was drawn into them between frames, so a popup or an overlay only has to be drawn when it changes
+#[derive(Clone, Debug, Default)]
+pub struct Compositor {
+    layers: Vec<(String, Layer)>,
+}
+
+impl Compositor {
+    pub const fn new() -> Self {
+        Self { layers: Vec::new() }
+    }
+
+    /// Adds a layer, replacing the one with the same name
+    pub fn insert(&mut self, name: impl Into<String>, layer: Layer) -> &mut Layer {
+        let name = name.into();
+        let index = match self.layers.iter().position(|(n, _)| *n == name) {
+            Some(index) => {
+                self.layers[index].1 = layer;
+                index
+            }
+            None => {
+                self.layers.push((name, layer));
+                self.layers.len() - 1
+            }
+        };
+        &mut self.layers[index].1
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Layer> {
+        let index = self.layers.iter().position(|(n, _)| n == name)?;
+        Some(self.layers.remove(index).1)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Layer> {
+        self.layers.iter().find(|(n, _)| n == name).map(|(_, l)| l)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Layer> {
+        self.layers
+            .iter_mut()
+            .find(|(n, _)| n == name)
+            .map(|(_, l)| l)
+    }
+
+    /// Every layer, in the order they were inserted
+    pub fn layers(&self) -> impl ExactSizeIterator<Item = (&str, &Layer)> {
+        self.layers.iter().map(|(n, l)| (n.as_str(), l))
+    }
+
+    /// Draws the visible layers over what's in the renderer, from the lowest z-index to the highest
+    ///
+    /// Only the cells drawn into since a layer was cleared are visited, and of those the fully transparent
+    /// ones are left alone, so they don't count as damage in the renderer
+    pub fn flatten(&self, renderer: &mut impl Renderer) {
+        let defaults = renderer.default_colors();
+
+        let mut layers = self
+            .layers
+            .iter()
+            .map(|(_, layer)| layer)
+            .filter(|layer| layer.visible && layer.opacity > 0.0)
+            .collect::<Vec<_>>();
+        layers.sort_by_key(|layer| layer.z_index);
+
+        for layer in layers {
+            for (y, cols) in layer.surface.damage() {
+                let Some(row) = layer.surface.row(y) else {
+                    continue;
+                };
+
+                for x in cols {
+                    let pos = layer.pos + Position::new(x as i32, y as i32);
+                    let Some(under) = renderer.get(pos) else {
+                        continue;
+                    };
+
+                    let over = &row[x as usize];
+                    let pixel = match layer.blend {
+                        BlendMode::Replace => Some(over.clone()),
+                        BlendMode::Blend => layer.composite(under, over, defaults),
+                    };
+                    if let Some(pixel) = pixel {
+                        renderer.put(pos, pixel, BlendMode::Replace);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn transparent() -> Pixel {
+    Pixel::empty().bg(Rgba(0, 0, 0, 0))
+}
+
+/// Mixes `over` into `under` by `alpha`, each with the color its [`Color::Default`] stands for
+///
+/// If either can't be resolved to a color, whichever covers more wins
+fn mix(under: (Color, Color), over: (Color, Color), alpha: f32) -> Color {
+    if alpha >= 1.0 {
+        return over.0;
+    }
+    if alpha <= 0.0 {
+        return under.0;
+    }
+
+    match (resolve(under), resolve(over)) {
+        (Some(left), Some(right)) => Color::Rgba(left.blend_linear(right, alpha)),
+        _ if alpha >= 0.5 => over.0,
+        _ => under.0,
+    }
+}
+
+fn resolve((color, default): (Color, Color)) -> Option<Rgba> {
+    match color.get_or_default(default) {
+        Color::Named(color) => Some(color.to_rgb()),
+        Color::Rgba(color) => Some(color),
+        Color::Default => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Attributes, BasicRenderer};
+
+    const BLACK: Rgba = Rgba(0, 0, 0, 255);
+
+    fn under() -> Pixel {
+        let mut pixel = Pixel::new('x').fg(Rgba(0, 200, 0, 255)).bg(BLACK);
+        pixel.set_attribute(Attributes::BOLD);
+        pixel
+    }
+
+    // four cells of `under`
+    fn target() -> BasicRenderer {
+        let mut renderer = BasicRenderer::new(Size::new(4, 1));
+        for x in 0..4 {
+            renderer.put(Position::new(x, 0), under(), BlendMode::Replace);
+        }
+        renderer
+    }
+
+    fn layer(size: Size, pos: Position, pixel: Pixel) -> Layer {
+        let mut layer = Layer::new(size).with_position(pos);
+        layer.surface_mut().fill(pixel);
+        layer
+    }
+
+    fn cells(renderer: &BasicRenderer) -> Vec<Pixel> {
+        let Size { width, height } = renderer.size();
+        (0..height as i32)
+            .flat_map(|y| (0..width as i32).map(move |x| Position::new(x, y)))
+            .map(|pos| renderer.get(pos).unwrap().clone())
+            .collect()
+    }
+
+    fn text(renderer: &BasicRenderer) -> String {
+        cells(renderer)
+            .into_iter()
+            .map(|pixel| match pixel.data {
+                PixelData::Char(ch) => ch.to_string(),
+                PixelData::Str(s) => s.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn overlapping_half_opacity() {
+        let mut compositor = Compositor::new();
+        compositor.insert(
+            "a",
+            layer(
+                Size::new(2, 1),
+                Position::new(0, 0),
+                Pixel::new('a')
+                    .fg(Rgba(100, 100, 100, 255))
+                    .bg(Rgba(200, 0, 0, 255)),
+            )
+            .with_opacity(0.5),
+        );
+        compositor.insert(
+            "b",
+            layer(
+                Size::new(2, 1),
+                Position::new(1, 0),
+                Pixel::new('b')
+                    .fg(Rgba(150, 100, 100, 255))
+                    .bg(Rgba(0, 0, 200, 255)),
+            )
+            .with_opacity(0.5)
+            .with_z_index(1),
+        );
+
+        let mut renderer = target();
+        compositor.flatten(&mut renderer);
+
+        // the background is mixed halfway, then the text halfway over that
+        assert_eq!(
+            cells(&renderer),
+            [
+                Pixel::new('a')
+                    .fg(Rgba(100, 50, 50, 255))
+                    .bg(Rgba(100, 0, 0, 255)),
+                Pixel::new('b')
+                    .fg(Rgba(100, 50, 100, 255))
+                    .bg(Rgba(50, 0, 100, 255)),
+                Pixel::new('b')
+                    .fg(Rgba(75, 50, 100, 255))
+                    .bg(Rgba(0, 0, 100, 255)),
+                under(),
+            ]
+        );
+    }
+
+    #[test]
+    fn blank_cells_tint_what_is_under_them() {
+        let mut compositor = Compositor::new();
+        let mut tint = Layer::new(Size::new(4, 1)).with_opacity(0.5);
+        tint.surface_mut()
+            .set(Position::new(1, 0), Pixel::empty().bg(Rgba(0, 0, 200, 255)));
+        // fully transparent, so it's left alone
+        tint.surface_mut().set(Position::new(2, 0), transparent());
+        compositor.insert("tint", tint);
+
+        let mut renderer = target();
+        compositor.flatten(&mut renderer);
+
+        let mut tinted = Pixel::new('x')
+            .fg(Rgba(0, 100, 100, 255))
+            .bg(Rgba(0, 0, 100, 255));
+        tinted.set_attribute(Attributes::BOLD);
+        assert_eq!(cells(&renderer), [under(), tinted, under(), under()]);
+    }
+
+    #[test]
+    fn translucent_background() {
+        let mut compositor = Compositor::new();
+        compositor.insert(
+            "glass",
+            layer(
+                Size::new(1, 1),
+                Position::new(0, 0),
+                Pixel::new('g')
+                    .fg(Rgba(200, 200, 200, 255))
+                    .bg(Rgba(200, 0, 0, 0)),
+            ),
+        );
+
+        let mut renderer = target();
+        compositor.flatten(&mut renderer);
+
+        // the background lets what's under it through, the text is drawn as it is
+        assert_eq!(
+            cells(&renderer)[0],
+            Pixel::new('g').fg(Rgba(200, 200, 200, 255)).bg(BLACK)
+        );
+    }
+
+    #[test]
+    fn default_colors_are_resolved() {
+        let mut compositor = Compositor::new();
+        compositor.insert(
+            "a",
+            layer(
+                Size::new(1, 1),
+                Position::new(0, 0),
+                Pixel::new('a').bg(Rgba(200, 0, 0, 255)),
+            )
+            .with_opacity(0.5),
+        );
+
+        let mut renderer = BasicRenderer::new(Size::new(1, 1))
+            .default_fg(Rgba(0, 0, 200, 255))
+            .default_bg(Rgba(0, 0, 200, 255));
+        compositor.flatten(&mut renderer);
+        assert_eq!(
+            cells(&renderer),
+            [Pixel::new('a')
+                .fg(Rgba(50, 0, 150, 255))
+                .bg(Rgba(100, 0, 100, 255))]
+        );
+
+        // without a color for the defaults, whichever covers more wins
+        let mut renderer = BasicRenderer::new(Size::new(1, 1));
+        compositor.flatten(&mut renderer);
+        assert_eq!(cells(&renderer), [Pixel::new('a').bg(Rgba(200, 0, 0, 255))]);
+    }
+
+    #[test]
+    fn replace() {
+        let over = Pixel::new('r').fg(Rgba(1, 2, 3, 255)).bg(Rgba(4, 5, 6, 7));
+        let mut compositor = Compositor::new();
+        compositor.insert(
+            "replace",
+            layer(Size::new(2, 1), Position::new(1, 0), over.clone())
+                .with_blend(BlendMode::Replace)
+                .with_opacity(0.5),
+        );
+
+        let mut renderer = target();
+        compositor.flatten(&mut renderer);
+        assert_eq!(text(&renderer), "xrrx");
+        assert_eq!(cells(&renderer)[1], over);
+    }
+
+    #[test]
+    fn z_order() {
+        let opaque = |ch| Pixel::new(ch).bg(BLACK);
+        let mut compositor = Compositor::new();
+        compositor.insert(
+            "top",
+            layer(Size::new(1, 1), Position::new(0, 0), opaque('t')).with_z_index(1),
+        );
+        compositor.insert(
+            "first",
+            layer(Size::new(2, 1), Position::new(0, 0), opaque('1')),
+        );
+        compositor.insert(
+            "second",
+            layer(Size::new(2, 1), Position::new(1, 0), opaque('2')),
+        );
+
+        // ties go by insertion order, and the higher z-index is drawn last even though it was inserted first
+        let mut renderer = target();
+        compositor.flatten(&mut renderer);
+        assert_eq!(text(&renderer), "t22x");
+
+        // replacing a layer keeps its place, so it's still under the second one
+        compositor.insert(
+            "first",
+            layer(Size::new(3, 1), Position::new(0, 0), opaque('3')),
+        );
+        let mut renderer = target();
+        compositor.flatten(&mut renderer);
+        assert_eq!(text(&renderer), "t22x");
+
+        compositor.get_mut("second").unwrap().set_z_index(-1);
+        let mut renderer = target();
+        compositor.flatten(&mut renderer);
+        assert_eq!(text(&renderer), "t33x");
+    }
+
+    #[test]
+    fn hidden_layers() {
+        let mut compositor = Compositor::new();
+        compositor.insert(
+            "hidden",
+            layer(
+                Size::new(1, 1),
+                Position::new(0, 0),
+                Pixel::new('h').bg(BLACK),
+            ),
+        );
+        compositor.get_mut("hidden").unwrap().set_visible(false);
+        compositor.insert(
+            "invisible",
+            layer(
+                Size::new(1, 1),
+                Position::new(1, 0),
+                Pixel::new('i').bg(BLACK),
+            )
+            .with_opacity(0.0),
+        );
+        compositor.insert(
+            "replace",
+            layer(Size::new(1, 1), Position::new(2, 0), Pixel::new('r'))
+                .with_blend(BlendMode::Replace)
+                .with_opacity(0.0),
+        );
+
+        let mut renderer = target();
+        compositor.flatten(&mut renderer);
+        assert_eq!(text(&renderer), "xxxx");
+
+        compositor.get_mut("hidden").unwrap().set_visible(true);
+        let mut renderer = target();
+        compositor.flatten(&mut renderer);
+        assert_eq!(text(&renderer), "hxxx");
+    }
+
+    #[test]
+    fn partially_outside() {
+        let mut compositor = Compositor::new();
+        let mut left = Layer::new(Size::new(3, 3)).with_position(Position::new(-2, -1));
+        for (pos, ch) in [((1, 1), 'a'), ((2, 1), 'b'), ((2, 2), 'c'), ((0, 0), 'z')] {
+            let pos = Position::new(pos.0, pos.1);
+            left.surface_mut().set(pos, Pixel::new(ch).bg(BLACK));
+        }
+        compositor.insert("left", left);
+        compositor.insert(
+            "right",
+            layer(
+                Size::new(3, 2),
+                Position::new(3, 0),
+                Pixel::new('r').bg(BLACK),
+            ),
+        );
+
+        let mut renderer = target();
+        compositor.flatten(&mut renderer);
+        assert_eq!(text(&renderer), "bxxr");
+    }
+
+    #[test]
+    fn only_drawn_cells_are_flattened() {
+        let mut layer = Layer::new(Size::new(4, 1)).with_blend(BlendMode::Replace);
+        assert!(!layer.surface().is_damaged());
+        layer
+            .surface_mut()
+            .set(Position::new(1, 0), Pixel::new('r').bg(BLACK));
+
+        let mut compositor = Compositor::new();
+        compositor.insert("layer", layer);
+
+        // the layer keeps its damage, so it's flattened the same way every frame
+        for _ in 0..2 {
+            let mut renderer = target();
+            compositor.flatten(&mut renderer);
+            assert_eq!(text(&renderer), "xrxx");
+        }
+
+        compositor.get_mut("layer").unwrap().clear();
+        let mut renderer = target();
+        compositor.flatten(&mut renderer);
+        assert_eq!(text(&renderer), "xxxx");
+    }
+}
@@ -0,0 +1,52 @@
+use mars_math::Position;
+
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq, Hash)]
+pub enum CursorShape {
+    /// Whatever the terminal is configured to use
+    #[default]
+    Default,
+    Block,
+    Underline,
+    Bar,
+}
+
+impl CursorShape {
+    /// The DECSCUSR parameter for this shape
+    pub const fn style(&self, blinking: bool) -> u8 {
+        let base = match self {
+            Self::Default => return 0,
+            Self::Block => 1,
+            Self::Underline => 3,
+            Self::Bar => 5,
+        };
+        if blinking { base } else { base + 1 }
+    }
+}
+
+/// The terminal's cursor, e.g. placed at the caret of a text input
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Cursor {
+    pub pos: Position,
+    pub shape: CursorShape,
+    pub blinking: bool,
+}
+
+impl Cursor {
+    pub const fn new(pos: Position) -> Self {
+        Self {
+            pos,
+            shape: CursorShape::Default,
+            blinking: false,
+        }
+    }
+
+    pub const fn shape(mut self, shape: CursorShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    pub const fn blinking(mut self, blinking: bool) -> Self {
+        self.blinking = blinking;
+        self
+    }
+}
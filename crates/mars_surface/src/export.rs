@@ -0,0 +1,447 @@
+use std::fmt::Write as _;
+
+use crate::{Attributes, Color, Pixel, Rgba, Surface, pixel::PixelData};
+
+/// Exports a [`Surface<Pixel>`] to standalone HTML or SVG, e.g. for documentation
+///
+/// Unlike a terminal the output can't know what [`Color::Default`] looks like, so that's configured here
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Export {
+    default_fg: Rgba,
+    default_bg: Rgba,
+    font_size: f32,
+}
+
+impl Default for Export {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Export {
+    /// The width of a cell, relative to the font size
+    const CELL_WIDTH: f32 = 0.6;
+    /// The height of a cell, relative to the font size
+    const CELL_HEIGHT: f32 = 1.2;
+
+    pub const fn new() -> Self {
+        Self {
+            default_fg: Rgba(0xD0, 0xD0, 0xD0, 0xFF),
+            default_bg: Rgba(0x00, 0x00, 0x00, 0xFF),
+            font_size: 14.0,
+        }
+    }
+
+    /// The color used for [`Color::Default`] foregrounds, e.g. the renderer's default foreground
+    ///
+    /// Passing [`Color::Default`] keeps the current color
+    pub fn default_fg(mut self, default_fg: impl Into<Color>) -> Self {
+        self.default_fg = resolve(default_fg.into(), self.default_fg);
+        self
+    }
+
+    /// The color used for [`Color::Default`] backgrounds, e.g. the renderer's default background
+    ///
+    /// Passing [`Color::Default`] keeps the current color
+    pub fn default_bg(mut self, default_bg: impl Into<Color>) -> Self {
+        self.default_bg = resolve(default_bg.into(), self.default_bg);
+        self
+    }
+
+    pub const fn font_size(mut self, font_size: f32) -> Self {
+        self.font_size = font_size;
+        self
+    }
+
+    /// A standalone HTML page, with a `<pre>` of styled spans
+    pub fn html(&self, surface: &Surface<Pixel>) -> String {
+        let mut out = String::new();
+        _ = write!(
+            &mut out,
+            "<!DOCTYPE html>\n\
+            <html>\n\
+            <head><meta charset=\"utf-8\"></head>\n\
+            <body style=\"margin: 0\">\n\
+            <pre style=\"display: inline-block; margin: 0; font-family: monospace; \
+            font-size: {}px; line-height: {}; color: {}; background: {}\">",
+            self.font_size,
+            Self::CELL_HEIGHT,
+            css(self.default_fg),
+            css(self.default_bg),
+        );
+
+        for (_, row) in surface.rows() {
+            for run in row.chunk_by(|l, r| style_of(l) == style_of(r)) {
+                let text = escape(&text_of(run));
+                let style = self.style(&run[0]);
+                if style == self.unstyled() {
+                    out.push_str(&text);
+                    continue;
+                }
+
+                out.push_str("<span style=\"");
+                if style.fg != self.default_fg {
+                    _ = write!(&mut out, "color: {}; ", css(style.fg));
+                }
+                if style.bg != self.default_bg {
+                    _ = write!(&mut out, "background: {}; ", css(style.bg));
+                }
+                if style.bold {
+                    out.push_str("font-weight: bold; ");
+                }
+                if style.italic {
+                    out.push_str("font-style: italic; ");
+                }
+                if let Some(decoration) = style.decoration() {
+                    _ = write!(&mut out, "text-decoration: {decoration}; ");
+                }
+                out.truncate(out.trim_end().len());
+                _ = write!(&mut out, "\">{text}</span>");
+            }
+            out.push('\n');
+        }
+
+        out.push_str("</pre>\n</body>\n</html>\n");
+        out
+    }
+
+    /// A standalone SVG image, with the text laid out on a monospace grid
+    pub fn svg(&self, surface: &Surface<Pixel>) -> String {
+        let cell_width = self.font_size * Self::CELL_WIDTH;
+        let cell_height = self.font_size * Self::CELL_HEIGHT;
+        let size = surface.size();
+        let (width, height) = (
+            num(size.width as f32 * cell_width),
+            num(size.height as f32 * cell_height),
+        );
+
+        let mut out = String::new();
+        _ = writeln!(
+            &mut out,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+            viewBox=\"0 0 {width} {height}\" font-family=\"monospace\" font-size=\"{}\">",
+            num(self.font_size)
+        );
+        _ = writeln!(
+            &mut out,
+            "<rect width=\"100%\" height=\"100%\" fill=\"{}\"/>",
+            css(self.default_bg)
+        );
+
+        // the backgrounds go first so they don't cover any text
+        let mut text = String::new();
+        for (y, row) in surface.rows() {
+            let mut x = 0;
+            for run in row.chunk_by(|l, r| style_of(l) == style_of(r)) {
+                let (left, cells) = (
+                    num(x as f32 * cell_width),
+                    num(run.len() as f32 * cell_width),
+                );
+                let top = y as f32 * cell_height;
+                x += run.len();
+
+                let style = self.style(&run[0]);
+                if style.bg != self.default_bg {
+                    _ = writeln!(
+                        &mut out,
+                        "<rect x=\"{left}\" y=\"{}\" width=\"{cells}\" height=\"{}\" fill=\"{}\"/>",
+                        num(top),
+                        num(cell_height),
+                        css(style.bg)
+                    );
+                }
+
+                let content = text_of(run);
+                if content.trim().is_empty() {
+                    continue;
+                }
+
+                // the baseline sits about three quarters of the way down the cell
+                _ = write!(
+                    &mut text,
+                    "<text x=\"{left}\" y=\"{}\" textLength=\"{cells}\" fill=\"{}\"",
+                    num(top + cell_height * 0.75),
+                    css(style.fg)
+                );
+                if style.bold {
+                    text.push_str(" font-weight=\"bold\"");
+                }
+                if style.italic {
+                    text.push_str(" font-style=\"italic\"");
+                }
+                if let Some(decoration) = style.decoration() {
+                    _ = write!(&mut text, " text-decoration=\"{decoration}\"");
+                }
+                _ = writeln!(
+                    &mut text,
+                    " xml:space=\"preserve\">{}</text>",
+                    escape(&content)
+                );
+            }
+        }
+
+        out.push_str(&text);
+        out.push_str("</svg>\n");
+        out
+    }
+
+    fn unstyled(&self) -> CellStyle {
+        CellStyle {
+            fg: self.default_fg,
+            bg: self.default_bg,
+            bold: false,
+            italic: false,
+            underline: false,
+            strikeout: false,
+        }
+    }
+
+    fn style(&self, pixel: &Pixel) -> CellStyle {
+        let attrs = pixel.attributes.filter(|&attrs| attrs != Attributes::RESET);
+        let has = |check: fn(&Attributes) -> bool| attrs.as_ref().is_some_and(check);
+
+        let mut fg = resolve(pixel.foreground, self.default_fg);
+        let mut bg = resolve(pixel.background, self.default_bg);
+        if has(Attributes::is_reverse) {
+            std::mem::swap(&mut fg, &mut bg);
+        }
+        // an opacity would fade the background along with the text
+        if has(Attributes::is_faint) {
+            fg = fg.blend_linear(bg, 0.5);
+        }
+
+        CellStyle {
+            fg,
+            bg,
+            bold: has(Attributes::is_bold),
+            italic: has(Attributes::is_italic),
+            underline: has(Attributes::is_underline),
+            strikeout: has(Attributes::is_strikeout),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct CellStyle {
+    fg: Rgba,
+    bg: Rgba,
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    strikeout: bool,
+}
+
+impl CellStyle {
+    const fn decoration(&self) -> Option<&'static str> {
+        match (self.underline, self.strikeout) {
+            (true, true) => Some("underline line-through"),
+            (true, false) => Some("underline"),
+            (false, true) => Some("line-through"),
+            (false, false) => None,
+        }
+    }
+}
+
+fn style_of(pixel: &Pixel) -> (Color, Color, Option<Attributes>) {
+    (pixel.foreground, pixel.background, pixel.attributes)
+}
+
+fn resolve(color: Color, default: Rgba) -> Rgba {
+    match color {
+        Color::Named(color) => color.to_rgb(),
+        Color::Rgba(color) => color,
+        Color::Default => default,
+    }
+}
+
+fn text_of(run: &[Pixel]) -> String {
+    let mut out = String::new();
+    for pixel in run {
+        match &pixel.data {
+            PixelData::Char(ch) => out.push(*ch),
+            PixelData::Str(s) => out.push_str(s),
+        }
+    }
+    out
+}
+
+/// Rounds to hundredths, so the output doesn't carry float noise like `33.600002`
+fn num(value: f32) -> String {
+    let out = format!("{value:.2}");
+    out.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+fn css(Rgba(r, g, b, a): Rgba) -> String {
+    match a {
+        0xFF => format!("#{r:02x}{g:02x}{b:02x}"),
+        a => format!("#{r:02x}{g:02x}{b:02x}{a:02x}"),
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            ch => out.push(ch),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use mars_math::{Position, Size};
+
+    use super::*;
+    use crate::IndexedColor;
+
+    fn styled(ch: char, attributes: Attributes) -> Pixel {
+        let mut pixel = Pixel::new(ch);
+        pixel.set_attribute(attributes);
+        pixel
+    }
+
+    // a<&
+    // rf"
+    fn surface() -> Surface<Pixel> {
+        let mut surface = Surface::new(Size::new(3, 2), Pixel::empty());
+        let cells = [
+            styled('a', Attributes::BOLD | Attributes::ITALIC).fg(Rgba(0xFF, 0x00, 0x00, 0xFF)),
+            Pixel::new('<'),
+            Pixel::new('&'),
+            styled('r', Attributes::REVERSE).bg(Rgba(0x00, 0x00, 0x80, 0xFF)),
+            styled('f', Attributes::FAINT),
+            styled('"', Attributes::UNDERLINE | Attributes::STRIKEOUT),
+        ];
+        for (i, pixel) in cells.into_iter().enumerate() {
+            surface.set(Position::new(i as i32 % 3, i as i32 / 3), pixel);
+        }
+        surface
+    }
+
+    #[test]
+    fn html() {
+        let expected = "\
+<!DOCTYPE html>
+<html>
+<head><meta charset=\"utf-8\"></head>
+<body style=\"margin: 0\">
+<pre style=\"display: inline-block; margin: 0; font-family: monospace; font-size: 14px; line-height: 1.2; \
+color: #d0d0d0; background: #000000\">\
+<span style=\"color: #ff0000; font-weight: bold; font-style: italic;\">a</span>&lt;&amp;
+<span style=\"color: #000080; background: #d0d0d0;\">r</span>\
+<span style=\"color: #686868;\">f</span>\
+<span style=\"text-decoration: underline line-through;\">&quot;</span>
+</pre>
+</body>
+</html>
+";
+        assert_eq!(Export::new().html(&surface()), expected);
+    }
+
+    #[test]
+    fn svg() {
+        let expected = "\
+<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"25.2\" height=\"33.6\" viewBox=\"0 0 25.2 33.6\" \
+font-family=\"monospace\" font-size=\"14\">
+<rect width=\"100%\" height=\"100%\" fill=\"#000000\"/>
+<rect x=\"0\" y=\"16.8\" width=\"8.4\" height=\"16.8\" fill=\"#d0d0d0\"/>
+<text x=\"0\" y=\"12.6\" textLength=\"8.4\" fill=\"#ff0000\" font-weight=\"bold\" font-style=\"italic\" \
+xml:space=\"preserve\">a</text>
+<text x=\"8.4\" y=\"12.6\" textLength=\"16.8\" fill=\"#d0d0d0\" xml:space=\"preserve\">&lt;&amp;</text>
+<text x=\"0\" y=\"29.4\" textLength=\"8.4\" fill=\"#000080\" xml:space=\"preserve\">r</text>
+<text x=\"8.4\" y=\"29.4\" textLength=\"8.4\" fill=\"#686868\" xml:space=\"preserve\">f</text>
+<text x=\"16.8\" y=\"29.4\" textLength=\"8.4\" fill=\"#d0d0d0\" text-decoration=\"underline line-through\" \
+xml:space=\"preserve\">&quot;</text>
+</svg>
+";
+        assert_eq!(Export::new().svg(&surface()), expected);
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(
+            escape(r#"<a href="x">&amp;</a> 'ok'"#),
+            "&lt;a href=&quot;x&quot;&gt;&amp;amp;&lt;/a&gt; 'ok'"
+        );
+
+        let mut surface = Surface::new(Size::new(3, 1), Pixel::empty());
+        surface.set(Position::new(0, 0), Pixel::new_str("<>"));
+        surface.set(Position::new(1, 0), Pixel::new('"'));
+        let export = Export::new();
+        assert!(export.html(&surface).contains("&lt;&gt;&quot; \n"));
+        assert!(export.svg(&surface).contains(">&lt;&gt;&quot; </text>"));
+    }
+
+    #[test]
+    fn reverse_video() {
+        let export = Export::new()
+            .default_fg(Rgba(0xAA, 0xAA, 0xAA, 0xFF))
+            .default_bg(Rgba(0x11, 0x11, 0x11, 0xFF));
+
+        let style = export.style(&styled('r', Attributes::REVERSE));
+        assert_eq!(
+            (style.fg, style.bg),
+            (Rgba(0x11, 0x11, 0x11, 0xFF), Rgba(0xAA, 0xAA, 0xAA, 0xFF))
+        );
+
+        let pixel = styled('r', Attributes::REVERSE).fg(Rgba(0xFF, 0x00, 0x00, 0xFF));
+        let style = export.style(&pixel);
+        assert_eq!(
+            (style.fg, style.bg),
+            (Rgba(0x11, 0x11, 0x11, 0xFF), Rgba(0xFF, 0x00, 0x00, 0xFF))
+        );
+
+        // faint fades what ends up as the foreground, towards what ends up as the background
+        let pixel = styled('r', Attributes::REVERSE | Attributes::FAINT)
+            .fg(Rgba(0xFF, 0xFF, 0xFF, 0xFF))
+            .bg(Rgba(0x00, 0x00, 0x00, 0xFF));
+        let style = export.style(&pixel);
+        assert_eq!(
+            (style.fg, style.bg),
+            (Rgba(0x80, 0x80, 0x80, 0xFF), Rgba(0xFF, 0xFF, 0xFF, 0xFF))
+        );
+
+        // a reset is no attributes at all
+        let style = export.style(&styled('r', Attributes::RESET));
+        assert_eq!(style, export.unstyled());
+    }
+
+    #[test]
+    fn default_colors() {
+        let export = Export::new()
+            .default_fg(IndexedColor::red())
+            .default_bg(Rgba(0x12, 0x34, 0x56, 0xFF))
+            // the default is what's being configured, so it changes nothing
+            .default_fg(Color::Default);
+        assert_eq!(export.default_fg, IndexedColor::red().to_rgb());
+        assert_eq!(export.default_bg, Rgba(0x12, 0x34, 0x56, 0xFF));
+
+        let style = export.style(&Pixel::new('x').bg(IndexedColor::blue()));
+        assert_eq!(style.fg, IndexedColor::red().to_rgb());
+        assert_eq!(style.bg, IndexedColor::blue().to_rgb());
+
+        let html = export.html(&Surface::new(Size::new(1, 1), Pixel::empty()));
+        let red = css(IndexedColor::red().to_rgb());
+        assert!(html.contains(&format!("color: {red}; background: #123456\"> \n")));
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(num(14.0), "14");
+        assert_eq!(num(100.0), "100");
+        assert_eq!(num(0.0), "0");
+        assert_eq!(num(0.5), "0.5");
+        assert_eq!(num(8.4), "8.4");
+        assert_eq!(num(33.600002), "33.6");
+        assert_eq!(num(12.345678), "12.35");
+        assert_eq!(num(0.001), "0");
+
+        assert_eq!(css(Rgba(0x12, 0xAB, 0x00, 0xFF)), "#12ab00");
+        assert_eq!(css(Rgba(0x12, 0xAB, 0x00, 0x80)), "#12ab0080");
+    }
+}
@@ -7,8 +7,13 @@ pub use drawable::{Drawable, DrawableExt};
 mod surface;
 pub use surface::{ResizeMode, Surface};
 
+mod compositor;
+pub use compositor::{Compositor, Layer};
+
 mod rasterizer;
-pub use rasterizer::{BufferedRasterizer, DebugRasterizer, Rasterizer};
+pub use rasterizer::{
+    BufferedRasterizer, DebugOp, DebugRasterizer, DebugStats, Rasterizer, StreamingRasterizer,
+};
 
 mod pixel;
 pub use pixel::{Attributes, Pixel};
@@ -18,3 +23,12 @@ pub use styling::Style;
 
 mod color;
 pub use color::{Color, IndexedColor, Rgba};
+
+mod cursor;
+pub use cursor::{Cursor, CursorShape};
+
+mod snapshot;
+pub use snapshot::{Snapshot, UPDATE_SNAPSHOTS};
+
+mod export;
+pub use export::Export;
@@ -2,13 +2,13 @@ use std::num::NonZeroU16;
 
 use crate::{BlendMode, Color, Rgba};
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum PixelData {
     Char(char),
     Str(compact_str::CompactString),
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Pixel {
     pub(crate) data: PixelData,
     pub foreground: Color,
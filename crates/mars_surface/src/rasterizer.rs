@@ -1,6 +1,8 @@
+use std::ops::Range;
+
 use mars_math::{Position, Size};
 
-use crate::{Attributes, Color};
+use crate::{Attributes, Color, CursorShape};
 
 // TODO unsigned positions
 pub trait Rasterizer {
@@ -14,6 +16,15 @@ pub trait Rasterizer {
 
     fn move_to(&mut self, pos: Position) -> Result<(), Self::Error>;
 
+    /// Moves what's shown in `rows` up by `lines`, or down if it's negative. The rows that are exposed are blank
+    ///
+    /// This leaves the cursor at the top left of the screen, ignoring any origin, as resetting the scrolling margins does.
+    /// Returns whether it scrolled, by default it doesn't and the renderer repaints the rows instead
+    fn scroll(&mut self, rows: Range<u32>, lines: i32) -> Result<bool, Self::Error> {
+        _ = (rows, lines);
+        Ok(false)
+    }
+
     fn default_fg(&mut self, color: Color) -> Result<(), Self::Error>;
     fn default_bg(&mut self, color: Color) -> Result<(), Self::Error>;
 
@@ -31,6 +42,20 @@ pub trait Rasterizer {
     // fn reset_underline_color(&mut self) -> Result<(), Self::Error>;
 
     fn write(&mut self, data: &str) -> Result<(), Self::Error>;
+
+    /// The cursor is left alone by default, for rasterizers that don't have one
+    fn show_cursor(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn hide_cursor(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn set_cursor_shape(&mut self, shape: CursorShape, blinking: bool) -> Result<(), Self::Error> {
+        _ = (shape, blinking);
+        Ok(())
+    }
 }
 
 impl<T> Rasterizer for &mut T
@@ -64,6 +89,11 @@ where
         (**self).move_to(pos)
     }
 
+    #[inline(always)]
+    fn scroll(&mut self, rows: Range<u32>, lines: i32) -> Result<bool, Self::Error> {
+        (**self).scroll(rows, lines)
+    }
+
     #[inline(always)]
     fn default_fg(&mut self, color: Color) -> Result<(), Self::Error> {
         (**self).default_fg(color)
@@ -108,10 +138,28 @@ where
     fn write(&mut self, data: &str) -> Result<(), Self::Error> {
         (**self).write(data)
     }
+
+    #[inline(always)]
+    fn show_cursor(&mut self) -> Result<(), Self::Error> {
+        (**self).show_cursor()
+    }
+
+    #[inline(always)]
+    fn hide_cursor(&mut self) -> Result<(), Self::Error> {
+        (**self).hide_cursor()
+    }
+
+    #[inline(always)]
+    fn set_cursor_shape(&mut self, shape: CursorShape, blinking: bool) -> Result<(), Self::Error> {
+        (**self).set_cursor_shape(shape, blinking)
+    }
 }
 
 mod buffered_rasterizer;
 pub use buffered_rasterizer::BufferedRasterizer;
 
+mod streaming_rasterizer;
+pub use streaming_rasterizer::StreamingRasterizer;
+
 mod debug_rasterizer;
-pub use debug_rasterizer::DebugRasterizer;
+pub use debug_rasterizer::{DebugOp, DebugRasterizer, DebugStats};
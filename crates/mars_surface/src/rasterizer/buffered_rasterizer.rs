@@ -1,21 +1,23 @@
-use std::{
-    convert::Infallible,
-    io::{Cursor, Write as _},
-};
+use std::{convert::Infallible, io::Cursor, ops::Range};
 
 use mars_math::{Position, Size};
 
-use crate::{Attributes, Color, IndexedColor, Rasterizer, Rgba};
+use crate::{Attributes, Color, CursorShape, Rasterizer, StreamingRasterizer};
 
-macro_rules! csi {
-    ($($lit:literal),*) => {
-        concat!($("\x1b[",$lit),*).as_bytes()
-    };
+/// Collects a frame in memory, to be sent somewhere with [`BufferedRasterizer::copy_to`]
+///
+/// This encodes the same way as [`StreamingRasterizer`], but can't fail until it's copied out.
+/// If encoding the frame failed, the error is returned by [`BufferedRasterizer::copy_to`] instead
+#[derive(Debug)]
+pub struct BufferedRasterizer {
+    inner: StreamingRasterizer<Cursor<Vec<u8>>>,
+    error: Option<std::io::Error>,
 }
 
-#[derive(Default)]
-pub struct BufferedRasterizer {
-    inner: Cursor<Vec<u8>>,
+impl Default for BufferedRasterizer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl BufferedRasterizer {
@@ -28,41 +30,68 @@ impl BufferedRasterizer {
     }
 
     const fn with_cursor(inner: Cursor<Vec<u8>>) -> Self {
-        Self { inner }
+        Self {
+            inner: StreamingRasterizer::new(inner),
+            error: None,
+        }
+    }
+
+    /// Offsets every position, e.g. to draw into an inline viewport
+    pub const fn set_origin(&mut self, origin: Position) {
+        self.inner.set_origin(origin);
+    }
+
+    pub const fn origin(&self) -> Position {
+        self.inner.origin()
+    }
+
+    /// How many bytes are waiting to be copied
+    pub fn len(&self) -> usize {
+        self.inner.get_ref().position() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 
+    /// Sends the buffered frame, or the error that stopped it from being encoded
+    ///
+    /// Either way the buffer is empty afterwards, a frame that failed to encode is dropped
     pub fn copy_to(&mut self, mut out: impl std::io::Write) -> std::io::Result<()> {
-        let pos = self.inner.position() as usize;
-        self.inner.set_position(0);
+        keep_error(&mut self.error, self.inner.flush_pending());
+        let inner = self.inner.get_mut();
+        let pos = inner.position() as usize;
+        inner.set_position(0);
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
         if pos == 0 {
             return Ok(());
         }
 
-        let mut input = &self.inner.get_ref()[..pos];
+        let mut input = &inner.get_ref()[..pos];
         std::io::copy(&mut input, &mut out)?;
         out.flush()
     }
+}
 
-    fn write_bytes(&mut self, data: &[u8]) -> Result<(), <Self as Rasterizer>::Error> {
-        _ = self.inner.write_all(data);
-        Ok(())
-    }
-
-    fn write_fmt(&mut self, f: std::fmt::Arguments<'_>) -> Result<(), <Self as Rasterizer>::Error> {
-        _ = write!(&mut self.inner, "{f}");
-        Ok(())
+/// Holds on to the first error until the frame is copied out
+fn keep_error(error: &mut Option<std::io::Error>, result: std::io::Result<()>) {
+    if let Err(err) = result {
+        error.get_or_insert(err);
     }
 }
 
 impl std::io::Write for BufferedRasterizer {
     #[inline(always)]
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        self.inner.write(buf)
+        self.inner.write_raw(buf)?;
+        Ok(buf.len())
     }
 
     #[inline(always)]
     fn flush(&mut self) -> std::io::Result<()> {
-        self.inner.flush()
+        self.inner.get_mut().flush()
     }
 }
 
@@ -70,89 +99,101 @@ impl Rasterizer for BufferedRasterizer {
     type Error = Infallible;
 
     fn begin(&mut self) -> Result<(), Self::Error> {
-        self.write_bytes(csi!("?2026h"))
+        keep_error(&mut self.error, self.inner.begin());
+        Ok(())
     }
 
     fn end(&mut self) -> Result<(), Self::Error> {
-        self.write_bytes(csi!("?2026l"))?;
-        _ = self.inner.flush();
+        keep_error(&mut self.error, self.inner.end());
         Ok(())
     }
 
     fn clear(&mut self, pos: Position, size: Size) -> Result<(), Self::Error> {
-        self.write_fmt(format_args!(
-            "\x1b[{y};{x};{h};{w}$z",
-            y = pos.y + 1,
-            x = pos.x + 1,
-            h = size.height,
-            w = size.width
-        ))
+        keep_error(&mut self.error, self.inner.clear(pos, size));
+        Ok(())
     }
 
     fn clear_screen(&mut self, bg: Color, size: Size) -> Result<(), Self::Error> {
-        for y in 0..size.height {
-            self.move_to(Position::new(0, y as _))?;
-            self.set_bg(bg)?;
-            self.write_fmt(format_args!("\x1b[{width}@", width = size.width))?
-        }
+        keep_error(&mut self.error, self.inner.clear_screen(bg, size));
         Ok(())
     }
 
     fn move_to(&mut self, pos: Position) -> Result<(), Self::Error> {
-        self.write_fmt(format_args!("\x1b[{y};{x};H", y = pos.y + 1, x = pos.x + 1,))
+        keep_error(&mut self.error, self.inner.move_to(pos));
+        Ok(())
+    }
+
+    fn scroll(&mut self, rows: Range<u32>, lines: i32) -> Result<bool, Self::Error> {
+        match self.inner.scroll(rows, lines) {
+            Ok(scrolled) => Ok(scrolled),
+            // the frame is dropped when it's copied out, so whether it scrolled doesn't matter
+            Err(err) => {
+                keep_error(&mut self.error, Err(err));
+                Ok(true)
+            }
+        }
     }
 
     fn default_fg(&mut self, color: Color) -> Result<(), Self::Error> {
-        self.set_fg(color)
+        keep_error(&mut self.error, self.inner.default_fg(color));
+        Ok(())
     }
 
     fn default_bg(&mut self, color: Color) -> Result<(), Self::Error> {
-        self.set_bg(color)
+        keep_error(&mut self.error, self.inner.default_bg(color));
+        Ok(())
     }
 
     fn set_fg(&mut self, color: Color) -> Result<(), Self::Error> {
-        match color {
-            Color::Named(IndexedColor(color)) => self.write_fmt(format_args!("\x1b[38;5;{color}m")),
-            Color::Rgba(Rgba(r, g, b, _)) => self.write_fmt(format_args!("\x1b[38;2;{r};{g};{b}m")),
-            // BUG this shouldn't reset the fg
-            Color::Default => self.reset_fg(),
-        }
+        keep_error(&mut self.error, self.inner.set_fg(color));
+        Ok(())
     }
 
     fn set_bg(&mut self, color: Color) -> Result<(), Self::Error> {
-        match color {
-            Color::Named(IndexedColor(color)) => self.write_fmt(format_args!("\x1b[48;5;{color}m")),
-            Color::Rgba(Rgba(r, g, b, _)) => self.write_fmt(format_args!("\x1b[48;2;{r};{g};{b}m")),
-            // BUG this shouldn't reset the bg
-            Color::Default => self.reset_bg(),
-        }
+        keep_error(&mut self.error, self.inner.set_bg(color));
+        Ok(())
     }
 
     fn set_attribute(&mut self, attribute: Attributes) -> Result<(), Self::Error> {
-        let mut seen = false;
-        for i in attribute.as_indexed_bytes() {
-            seen = true;
-            self.write_fmt(format_args!("\x1b[{i}m"))?;
-        }
-        if !seen {
-            self.write_bytes(csi!("0m"))?;
-        }
+        keep_error(&mut self.error, self.inner.set_attribute(attribute));
         Ok(())
     }
 
     fn reset_fg(&mut self) -> Result<(), Self::Error> {
-        self.write_bytes(csi!("39m"))
+        keep_error(&mut self.error, self.inner.reset_fg());
+        Ok(())
     }
 
     fn reset_bg(&mut self) -> Result<(), Self::Error> {
-        self.write_bytes(csi!("49m"))
+        keep_error(&mut self.error, self.inner.reset_bg());
+        Ok(())
     }
 
     fn reset_attribute(&mut self) -> Result<(), Self::Error> {
-        self.write_bytes(csi!("59m"))
+        keep_error(&mut self.error, self.inner.reset_attribute());
+        Ok(())
     }
 
     fn write(&mut self, data: &str) -> Result<(), Self::Error> {
-        self.write_fmt(format_args!("{data}"))
+        keep_error(&mut self.error, self.inner.write(data));
+        Ok(())
+    }
+
+    fn show_cursor(&mut self) -> Result<(), Self::Error> {
+        keep_error(&mut self.error, self.inner.show_cursor());
+        Ok(())
+    }
+
+    fn hide_cursor(&mut self) -> Result<(), Self::Error> {
+        keep_error(&mut self.error, self.inner.hide_cursor());
+        Ok(())
+    }
+
+    fn set_cursor_shape(&mut self, shape: CursorShape, blinking: bool) -> Result<(), Self::Error> {
+        keep_error(
+            &mut self.error,
+            self.inner.set_cursor_shape(shape, blinking),
+        );
+        Ok(())
     }
 }
@@ -1,12 +1,113 @@
-use std::{convert::Infallible, fmt::Write as _};
+use std::{convert::Infallible, fmt::Write as _, ops::Range};
 
 use mars_math::{Position, Size};
+use unicode_segmentation::UnicodeSegmentation as _;
+use unicode_width::UnicodeWidthStr as _;
 
-use crate::{Attributes, Color, IndexedColor, Rasterizer, Rgba};
+use crate::{Attributes, BufferedRasterizer, Color, CursorShape, IndexedColor, Rasterizer, Rgba};
 
+/// A single call made to a [`DebugRasterizer`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DebugOp {
+    Begin,
+    End,
+    Clear {
+        pos: Position,
+        size: Size,
+    },
+    ClearScreen {
+        bg: Color,
+        size: Size,
+    },
+    MoveTo(Position),
+    Scroll {
+        rows: Range<u32>,
+        lines: i32,
+    },
+    DefaultFg(Color),
+    DefaultBg(Color),
+    SetFg(Color),
+    SetBg(Color),
+    SetAttribute(Attributes),
+    ResetFg,
+    ResetBg,
+    ResetAttribute,
+    /// Consecutive writes are joined together
+    Write(String),
+    ShowCursor,
+    HideCursor,
+    SetCursorShape {
+        shape: CursorShape,
+        blinking: bool,
+    },
+}
+
+impl DebugOp {
+    /// Makes the same call on another rasterizer
+    pub fn apply<R: Rasterizer>(&self, rasterizer: &mut R) -> Result<(), R::Error> {
+        match self {
+            Self::Begin => rasterizer.begin(),
+            Self::End => rasterizer.end(),
+            Self::Clear { pos, size } => rasterizer.clear(*pos, *size),
+            Self::ClearScreen { bg, size } => rasterizer.clear_screen(*bg, *size),
+            Self::MoveTo(pos) => rasterizer.move_to(*pos),
+            Self::Scroll { rows, lines } => rasterizer.scroll(rows.clone(), *lines).map(drop),
+            Self::DefaultFg(color) => rasterizer.default_fg(*color),
+            Self::DefaultBg(color) => rasterizer.default_bg(*color),
+            Self::SetFg(color) => rasterizer.set_fg(*color),
+            Self::SetBg(color) => rasterizer.set_bg(*color),
+            Self::SetAttribute(attribute) => rasterizer.set_attribute(*attribute),
+            Self::ResetFg => rasterizer.reset_fg(),
+            Self::ResetBg => rasterizer.reset_bg(),
+            Self::ResetAttribute => rasterizer.reset_attribute(),
+            Self::Write(data) => rasterizer.write(data),
+            Self::ShowCursor => rasterizer.show_cursor(),
+            Self::HideCursor => rasterizer.hide_cursor(),
+            Self::SetCursorShape { shape, blinking } => {
+                rasterizer.set_cursor_shape(*shape, *blinking)
+            }
+        }
+    }
+}
+
+/// Totals over the ops of a frame, for asserting on how efficiently it was rendered
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub struct DebugStats {
+    /// The bytes a [`BufferedRasterizer`] would have written, which is only exact once the frame has ended
+    pub bytes: usize,
+    pub moves: usize,
+    pub scrolls: usize,
+    /// Every set or reset of the foreground or background
+    pub color_changes: usize,
+    pub attribute_changes: usize,
+    pub writes: usize,
+    /// The display width of everything written
+    pub cells_written: usize,
+}
+
+impl DebugStats {
+    fn record(&mut self, op: &DebugOp) {
+        match op {
+            DebugOp::MoveTo(..) => self.moves += 1,
+            DebugOp::Scroll { .. } => self.scrolls += 1,
+            DebugOp::SetFg(..) | DebugOp::SetBg(..) | DebugOp::ResetFg | DebugOp::ResetBg => {
+                self.color_changes += 1
+            }
+            DebugOp::SetAttribute(..) | DebugOp::ResetAttribute => self.attribute_changes += 1,
+            _ => {}
+        }
+    }
+}
+
+/// Records what gets rasterized, as readable text (its [`Display`](std::fmt::Display)) and as a list of [`DebugOp`]s
+///
+/// Everything is cleared at [`Rasterizer::begin`], so it holds the last frame
 #[derive(Debug)]
 pub struct DebugRasterizer {
     out: String,
+    ops: Vec<DebugOp>,
+    stats: DebugStats,
+    encoder: BufferedRasterizer,
     incomplete: bool,
 }
 
@@ -20,10 +121,48 @@ impl DebugRasterizer {
     pub const fn new() -> Self {
         Self {
             out: String::new(),
+            ops: Vec::new(),
+            stats: DebugStats {
+                bytes: 0,
+                moves: 0,
+                scrolls: 0,
+                color_changes: 0,
+                attribute_changes: 0,
+                writes: 0,
+                cells_written: 0,
+            },
+            encoder: BufferedRasterizer::new(),
             incomplete: false,
         }
     }
 
+    pub fn ops(&self) -> &[DebugOp] {
+        &self.ops
+    }
+
+    pub const fn stats(&self) -> DebugStats {
+        self.stats
+    }
+
+    /// Everything written, in order
+    pub fn written(&self) -> impl Iterator<Item = &str> {
+        self.ops.iter().filter_map(|op| match op {
+            DebugOp::Write(data) => Some(data.as_str()),
+            _ => None,
+        })
+    }
+
+    fn push(&mut self, op: DebugOp) {
+        self.encode(&op);
+        self.stats.record(&op);
+        self.ops.push(op);
+    }
+
+    fn encode(&mut self, op: &DebugOp) {
+        let Ok(..) = op.apply(&mut self.encoder);
+        self.stats.bytes = self.encoder.len();
+    }
+
     fn next_entry(&mut self) {
         if self.incomplete {
             self.out.push('\n');
@@ -31,12 +170,7 @@ impl DebugRasterizer {
         }
     }
 
-    fn set_color(
-        &mut self,
-        name: &str,
-        color: Color,
-        or: impl Fn(&mut Self) -> Result<(), <Self as Rasterizer>::Error>,
-    ) -> Result<(), <Self as Rasterizer>::Error> {
+    fn set_color(&mut self, name: &str, color: Color) {
         match color {
             Color::Named(IndexedColor(index)) => {
                 _ = writeln!(&mut self.out, "    {name}: {index}");
@@ -44,10 +178,10 @@ impl DebugRasterizer {
             Color::Rgba(Rgba(r, g, b, a)) => {
                 _ = writeln!(&mut self.out, "    {name}: #{r:02X}{g:02X}{b:02X}{a:02X}");
             }
-            Color::Default => or(self)?,
+            Color::Default => {
+                _ = writeln!(&mut self.out, "    {name}: default");
+            }
         }
-
-        Ok(())
     }
 }
 
@@ -62,20 +196,25 @@ impl Rasterizer for DebugRasterizer {
 
     fn begin(&mut self) -> Result<(), Self::Error> {
         self.out.clear();
+        self.ops.clear();
+        self.stats = DebugStats::default();
+        _ = self.encoder.copy_to(std::io::sink());
         self.incomplete = false;
-        self.next_entry();
+        self.push(DebugOp::Begin);
         _ = writeln!(&mut self.out, "begin");
         Ok(())
     }
 
     fn end(&mut self) -> Result<(), Self::Error> {
         self.next_entry();
+        self.push(DebugOp::End);
         _ = writeln!(&mut self.out, "end");
         Ok(())
     }
 
     fn clear(&mut self, pos: Position, size: Size) -> Result<(), Self::Error> {
         self.next_entry();
+        self.push(DebugOp::Clear { pos, size });
         _ = writeln!(
             &mut self.out,
             "    clear {x},{y} .. {w},{h}",
@@ -87,39 +226,63 @@ impl Rasterizer for DebugRasterizer {
         Ok(())
     }
 
-    fn clear_screen(&mut self, bg: Color, _size: Size) -> Result<(), Self::Error> {
+    fn clear_screen(&mut self, bg: Color, size: Size) -> Result<(), Self::Error> {
         self.next_entry();
-        self.set_color("clear_screen", bg, |_| Ok(()))
+        self.push(DebugOp::ClearScreen { bg, size });
+        self.set_color("clear_screen", bg);
+        Ok(())
     }
 
     fn move_to(&mut self, pos: Position) -> Result<(), Self::Error> {
         self.next_entry();
+        self.push(DebugOp::MoveTo(pos));
         _ = writeln!(&mut self.out, "    move to: {x},{y}", x = pos.x, y = pos.y);
         Ok(())
     }
 
+    fn scroll(&mut self, rows: Range<u32>, lines: i32) -> Result<bool, Self::Error> {
+        self.next_entry();
+        _ = writeln!(
+            &mut self.out,
+            "    scroll {}..{} by {lines}",
+            rows.start, rows.end
+        );
+        self.push(DebugOp::Scroll { rows, lines });
+        Ok(true)
+    }
+
     fn default_fg(&mut self, color: Color) -> Result<(), Self::Error> {
         self.next_entry();
-        self.set_color("set_default_fg", color, |_| Ok(()))
+        self.push(DebugOp::DefaultFg(color));
+        self.set_color("set_default_fg", color);
+        Ok(())
     }
 
     fn default_bg(&mut self, color: Color) -> Result<(), Self::Error> {
         self.next_entry();
-        self.set_color("set_default_bg", color, |_| Ok(()))
+        self.push(DebugOp::DefaultBg(color));
+        self.set_color("set_default_bg", color);
+        Ok(())
     }
 
     fn set_fg(&mut self, color: Color) -> Result<(), Self::Error> {
         self.next_entry();
-        self.set_color("set_fg", color, |_| Ok(()))
+        self.push(DebugOp::SetFg(color));
+        self.set_color("set_fg", color);
+        Ok(())
     }
 
     fn set_bg(&mut self, color: Color) -> Result<(), Self::Error> {
         self.next_entry();
-        self.set_color("set_bg", color, |_| Ok(()))
+        self.push(DebugOp::SetBg(color));
+        self.set_color("set_bg", color);
+        Ok(())
     }
 
     fn set_attribute(&mut self, attribute: Attributes) -> Result<(), Self::Error> {
         self.next_entry();
+        self.push(DebugOp::SetAttribute(attribute));
+        _ = write!(&mut self.out, "    set_attribute: ");
         for (i, ul) in attribute.as_indexed_bytes().enumerate() {
             if i > 0 {
                 _ = write!(&mut self.out, " | ");
@@ -132,37 +295,168 @@ impl Rasterizer for DebugRasterizer {
 
     fn reset_fg(&mut self) -> Result<(), Self::Error> {
         self.next_entry();
-        _ = writeln!(&mut self.out, "   reset_fg");
+        self.push(DebugOp::ResetFg);
+        _ = writeln!(&mut self.out, "    reset_fg");
         Ok(())
     }
 
     fn reset_bg(&mut self) -> Result<(), Self::Error> {
         self.next_entry();
-        _ = writeln!(&mut self.out, "   reset_bg");
+        self.push(DebugOp::ResetBg);
+        _ = writeln!(&mut self.out, "    reset_bg");
         Ok(())
     }
 
     fn reset_attribute(&mut self) -> Result<(), Self::Error> {
         self.next_entry();
-        _ = writeln!(&mut self.out, "   reset_attribute");
+        self.push(DebugOp::ResetAttribute);
+        _ = writeln!(&mut self.out, "    reset_attribute");
         Ok(())
     }
 
     fn write(&mut self, data: &str) -> Result<(), Self::Error> {
+        let op = DebugOp::Write(data.to_string());
+        self.encode(&op);
+        self.stats.cells_written += data.width();
+        match self.ops.last_mut() {
+            Some(DebugOp::Write(last)) if self.incomplete => last.push_str(data),
+            _ => {
+                self.stats.writes += 1;
+                self.ops.push(op);
+            }
+        }
+
         if !std::mem::replace(&mut self.incomplete, true) {
             self.out.push_str("    ");
         }
 
-        // TODO grapheme clusters
-        for cluster in data.chars() {
-            let cluster = if cluster.is_whitespace() {
-                '▪'
+        // whitespace is made visible, one marker per cell it covers
+        for cluster in data.graphemes(true) {
+            if cluster.chars().all(char::is_whitespace) {
+                let width = cluster.width().max(1);
+                self.out.extend(std::iter::repeat_n('▪', width));
             } else {
-                cluster
-            };
-            _ = write!(&mut self.out, "{cluster}")
+                self.out.push_str(cluster);
+            }
         }
 
         Ok(())
     }
+
+    fn show_cursor(&mut self) -> Result<(), Self::Error> {
+        self.next_entry();
+        self.push(DebugOp::ShowCursor);
+        _ = writeln!(&mut self.out, "    show_cursor");
+        Ok(())
+    }
+
+    fn hide_cursor(&mut self) -> Result<(), Self::Error> {
+        self.next_entry();
+        self.push(DebugOp::HideCursor);
+        _ = writeln!(&mut self.out, "    hide_cursor");
+        Ok(())
+    }
+
+    fn set_cursor_shape(&mut self, shape: CursorShape, blinking: bool) -> Result<(), Self::Error> {
+        self.next_entry();
+        self.push(DebugOp::SetCursorShape { shape, blinking });
+        _ = writeln!(
+            &mut self.out,
+            "    cursor_shape: {shape:?} blinking: {blinking}"
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BasicRenderer, BlendMode, Pixel};
+
+    #[test]
+    fn stats() {
+        let mut out = DebugRasterizer::new();
+        let Ok(()) = (|| {
+            out.begin()?;
+            out.move_to(Position::new(1, 0))?;
+            out.set_fg(Color::Named(IndexedColor(1)))?;
+            out.set_bg(Color::Rgba(Rgba(0, 0, 255, 255)))?;
+            out.set_attribute(Attributes::BOLD)?;
+            out.write("ab")?;
+            out.write("c")?;
+            out.reset_fg()?;
+            out.reset_attribute()?;
+            out.move_to(Position::new(0, 1))?;
+            out.write("日")?;
+            out.scroll(0..2, 1)?;
+            out.end()
+        })();
+
+        // consecutive writes are merged into one
+        assert_eq!(out.written().collect::<Vec<_>>(), ["abc", "日"]);
+        let stats = out.stats();
+        assert_eq!(stats.moves, 2);
+        assert_eq!(stats.scrolls, 1);
+        assert_eq!(stats.color_changes, 3);
+        assert_eq!(stats.attribute_changes, 2);
+        assert_eq!(stats.writes, 2);
+        assert_eq!(stats.cells_written, 5);
+
+        // the bytes are what the same calls encode to
+        let mut encoder = BufferedRasterizer::new();
+        for op in out.ops() {
+            let Ok(()) = op.apply(&mut encoder);
+        }
+        assert_eq!(stats.bytes, encoder.len());
+
+        // and everything starts over with the next frame
+        let Ok(()) = out.begin();
+        assert_eq!(out.ops(), [DebugOp::Begin]);
+        assert_eq!(out.stats().writes, 0);
+        assert_eq!(out.to_string(), "begin\n");
+    }
+
+    #[test]
+    fn unchanged_frame() {
+        let mut renderer = BasicRenderer::new(Size::new(4, 2));
+        let draw = |renderer: &mut BasicRenderer| {
+            for (x, ch) in "hey".chars().enumerate() {
+                let pixel = Pixel::new(ch).fg(IndexedColor(2));
+                renderer.put(Position::new(x as i32, 1), pixel, BlendMode::Replace);
+            }
+        };
+
+        draw(&mut renderer);
+        let mut out = DebugRasterizer::new();
+        let Ok(()) = renderer.render(&mut out);
+        assert_eq!(out.written().collect::<String>().trim(), "hey");
+
+        draw(&mut renderer);
+        let Ok(()) = renderer.render(&mut out);
+        let stats = out.stats();
+        assert_eq!(stats.writes, 0);
+        assert_eq!(stats.cells_written, 0);
+        assert_eq!(stats.color_changes, 0);
+        // only the cursor is parked again
+        assert_eq!(
+            out.ops(),
+            [
+                DebugOp::Begin,
+                DebugOp::MoveTo(Position::ZERO),
+                DebugOp::End
+            ]
+        );
+    }
+
+    #[test]
+    fn whitespace_markers() {
+        let mut out = DebugRasterizer::new();
+        let Ok(()) = (|| {
+            out.begin()?;
+            // a wide space, a combining accent and CRLF, which is a single cluster
+            out.write("a \u{3000}e\u{301}\r\nb")?;
+            out.end()
+        })();
+        assert_eq!(out.to_string(), "begin\n    a▪▪▪e\u{301}▪b\nend\n");
+    }
 }
@@ -0,0 +1,704 @@
+use std::ops::Range;
+
+use mars_math::{Position, Size};
+use unicode_width::{UnicodeWidthChar as _, UnicodeWidthStr as _};
+
+use crate::{Attributes, Color, CursorShape, IndexedColor, Rasterizer, Rgba};
+
+macro_rules! csi {
+    ($($lit:literal),*) => {
+        concat!($("\x1b[",$lit),*).as_bytes()
+    };
+}
+
+/// Writes escape sequences straight to `out`, failing as soon as it does
+///
+/// Nothing is buffered here, so `out` should be, e.g. a terminal or a [`std::io::BufWriter`].
+/// `out` is flushed at [`Rasterizer::end`]
+///
+/// The output is kept small:
+/// - moves and colors are only sent once something is written, and only if they changed
+/// - moves are relative (`CR`, `LF`, `CUF`, ...) when that's shorter
+/// - colors and attributes are combined into a single `SGR`
+/// - runs of the same character are sent with `REP`, or `ECH` for blanks at the end of a run
+#[derive(Debug)]
+pub struct StreamingRasterizer<W> {
+    out: W,
+    origin: Position,
+    repeat: bool,
+    // where the terminal's cursor is, if we know
+    cursor: Option<Position>,
+    // where the next write goes, `None` is wherever the cursor is
+    target: Option<Position>,
+    // the cursor might be past the last column, so it can only be moved relatively with `CR`
+    wrap_pending: bool,
+    fg: Option<Color>,
+    bg: Option<Color>,
+    // nothing is set that shows up on blanks, e.g. underline
+    plain: bool,
+    sgr: Vec<u8>,
+    run: Option<Run>,
+    buf: Vec<u8>,
+    alt: Vec<u8>,
+}
+
+/// Cells written with the same character, that haven't been sent yet
+#[derive(Debug)]
+struct Run {
+    ch: char,
+    count: u32,
+    end: Position,
+    plain: bool,
+}
+
+impl<W: std::io::Write> StreamingRasterizer<W> {
+    pub const fn new(out: W) -> Self {
+        Self {
+            out,
+            origin: Position::ZERO,
+            repeat: true,
+            cursor: None,
+            target: None,
+            wrap_pending: false,
+            fg: None,
+            bg: None,
+            plain: false,
+            sgr: Vec::new(),
+            run: None,
+            buf: Vec::new(),
+            alt: Vec::new(),
+        }
+    }
+
+    /// Offsets every position, e.g. to draw into an inline viewport
+    pub const fn with_origin(mut self, origin: Position) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    /// Whether to use `REP` for repeated characters, this is on by default
+    ///
+    /// Not every terminal supports it (e.g. Apple's Terminal), without it only blanks are shortened
+    pub const fn repeat(mut self, repeat: bool) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    pub const fn set_origin(&mut self, origin: Position) {
+        self.origin = origin;
+    }
+
+    pub const fn origin(&self) -> Position {
+        self.origin
+    }
+
+    pub const fn get_ref(&self) -> &W {
+        &self.out
+    }
+
+    pub const fn get_mut(&mut self) -> &mut W {
+        &mut self.out
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    /// Writes bytes we can't keep track of, so afterwards nothing about the terminal is assumed
+    pub(crate) fn write_raw(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.flush_pending()?;
+        self.out.write_all(data)?;
+        self.forget();
+        Ok(())
+    }
+
+    /// Sends everything that's been held back, e.g. a move without a write following it
+    pub(crate) fn flush_pending(&mut self) -> std::io::Result<()> {
+        self.sync()?;
+        self.flush_sgr()
+    }
+
+    fn forget(&mut self) {
+        self.cursor = None;
+        self.target = None;
+        self.wrap_pending = false;
+        self.fg = None;
+        self.bg = None;
+        self.plain = false;
+    }
+
+    fn param(&mut self, param: u32) {
+        if !self.sgr.is_empty() {
+            self.sgr.push(b';');
+        }
+        push_u32(&mut self.sgr, param);
+    }
+
+    fn color(&mut self, base: u32, color: Color) {
+        match color {
+            Color::Named(IndexedColor(index)) => {
+                for param in [base, 5, index as u32] {
+                    self.param(param);
+                }
+            }
+            Color::Rgba(Rgba(r, g, b, _)) => {
+                for param in [base, 2, r as u32, g as u32, b as u32] {
+                    self.param(param);
+                }
+            }
+            // BUG this shouldn't reset the color
+            Color::Default => self.param(base + 1),
+        }
+    }
+
+    fn flush_sgr(&mut self) -> std::io::Result<()> {
+        if self.sgr.is_empty() {
+            return Ok(());
+        }
+        self.out.write_all(b"\x1b[")?;
+        self.out.write_all(&self.sgr)?;
+        self.out.write_all(b"m")?;
+        self.sgr.clear();
+        Ok(())
+    }
+
+    fn flush_run(&mut self) -> std::io::Result<()> {
+        let Some(run) = self.run.take() else {
+            return Ok(());
+        };
+
+        // blanks can be erased without moving the cursor, if it has to move somewhere else anyway
+        let continues = self.target == Some(run.end);
+        let n = run.count;
+        self.buf.clear();
+        if run.ch == ' ' && run.plain && !continues && csi_len(n) < n as usize {
+            push_csi(&mut self.buf, n, b'X');
+        } else {
+            let mut encoded = [0u8; 4];
+            let ch = run.ch.encode_utf8(&mut encoded).as_bytes();
+            self.buf.extend_from_slice(ch);
+
+            let rest = n - 1;
+            if self.repeat && rest > 0 && csi_len(rest) < rest as usize * ch.len() {
+                push_csi(&mut self.buf, rest, b'b');
+            } else {
+                for _ in 0..rest {
+                    self.buf.extend_from_slice(ch);
+                }
+            }
+
+            self.cursor = Some(run.end);
+            self.wrap_pending = true;
+        }
+        self.out.write_all(&self.buf)
+    }
+
+    /// Moves the cursor to where the next write goes
+    fn sync(&mut self) -> std::io::Result<()> {
+        self.flush_run()?;
+
+        let Some(target) = self.target else {
+            return Ok(());
+        };
+        // a pending wrap only matters when moving, the cursor can't be past the last column and where we want it
+        if self.cursor == Some(target) {
+            return Ok(());
+        }
+
+        self.buf.clear();
+        push_move_absolute(&mut self.buf, target);
+        if let Some(cursor) = self.cursor {
+            self.alt.clear();
+            push_move_relative(&mut self.alt, cursor, target, self.wrap_pending);
+            if self.alt.len() < self.buf.len() {
+                std::mem::swap(&mut self.buf, &mut self.alt);
+            }
+        }
+
+        self.cursor = Some(target);
+        self.wrap_pending = false;
+        self.out.write_all(&self.buf)
+    }
+}
+
+impl<W: std::io::Write> Rasterizer for StreamingRasterizer<W> {
+    type Error = std::io::Error;
+
+    fn begin(&mut self) -> Result<(), Self::Error> {
+        self.flush_pending()?;
+
+        // start from a known state, so unchanged colors don't have to be sent
+        self.out.write_all(csi!("?2026h", "0m"))?;
+        self.forget();
+        self.fg = Some(Color::Default);
+        self.bg = Some(Color::Default);
+        self.plain = true;
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<(), Self::Error> {
+        self.flush_pending()?;
+        self.out.write_all(csi!("?2026l"))?;
+        self.out.flush()
+    }
+
+    fn clear(&mut self, pos: Position, size: Size) -> Result<(), Self::Error> {
+        self.flush_run()?;
+        self.flush_sgr()?;
+
+        let pos = pos + self.origin;
+        let params = [pos.y + 1, pos.x + 1, size.height as i32, size.width as i32];
+        self.buf.clear();
+        self.buf.extend_from_slice(b"\x1b[");
+        for (i, n) in params.into_iter().enumerate() {
+            if i > 0 {
+                self.buf.push(b';');
+            }
+            push_u32(&mut self.buf, n.max(0) as u32);
+        }
+        self.buf.extend_from_slice(b"$z");
+        self.out.write_all(&self.buf)
+    }
+
+    fn clear_screen(&mut self, bg: Color, size: Size) -> Result<(), Self::Error> {
+        for y in 0..size.height {
+            self.move_to(Position::new(0, y as _))?;
+            self.set_bg(bg)?;
+            self.flush_pending()?;
+
+            self.buf.clear();
+            push_csi(&mut self.buf, size.width, b'@');
+            self.out.write_all(&self.buf)?;
+        }
+        Ok(())
+    }
+
+    fn move_to(&mut self, pos: Position) -> Result<(), Self::Error> {
+        self.target = Some(pos + self.origin);
+        Ok(())
+    }
+
+    fn scroll(&mut self, rows: Range<u32>, lines: i32) -> Result<bool, Self::Error> {
+        if rows.is_empty() || lines == 0 {
+            return Ok(true);
+        }
+        self.flush_run()?;
+        self.flush_sgr()?;
+
+        // DECSTBM, then SU or SD inside of it
+        let top = (rows.start as i32 + self.origin.y).max(0) as u32;
+        let bottom = (rows.end as i32 + self.origin.y).max(0) as u32;
+        self.buf.clear();
+        self.buf.extend_from_slice(b"\x1b[");
+        push_u32(&mut self.buf, top + 1);
+        self.buf.push(b';');
+        push_u32(&mut self.buf, bottom);
+        self.buf.push(b'r');
+        push_csi(
+            &mut self.buf,
+            lines.unsigned_abs(),
+            if lines > 0 { b'S' } else { b'T' },
+        );
+        // resetting the margins moves the cursor home, so line feeds can't scroll by accident later
+        self.buf.extend_from_slice(b"\x1b[r");
+        self.out.write_all(&self.buf)?;
+
+        // `cursor` is on the screen, so this is the top left even with an origin
+        self.cursor = Some(Position::ZERO);
+        self.wrap_pending = false;
+        Ok(true)
+    }
+
+    fn default_fg(&mut self, color: Color) -> Result<(), Self::Error> {
+        self.set_fg(color)
+    }
+
+    fn default_bg(&mut self, color: Color) -> Result<(), Self::Error> {
+        self.set_bg(color)
+    }
+
+    fn set_fg(&mut self, color: Color) -> Result<(), Self::Error> {
+        if self.fg != Some(color) {
+            self.fg = Some(color);
+            self.color(38, color);
+        }
+        Ok(())
+    }
+
+    fn set_bg(&mut self, color: Color) -> Result<(), Self::Error> {
+        if self.bg != Some(color) {
+            self.bg = Some(color);
+            self.color(48, color);
+        }
+        Ok(())
+    }
+
+    fn set_attribute(&mut self, attribute: Attributes) -> Result<(), Self::Error> {
+        let mut seen = false;
+        for i in attribute.as_indexed_bytes() {
+            seen = true;
+            self.param(i as u32);
+        }
+        if !seen {
+            // a reset undoes everything before it in the same `SGR`, so it goes first and the colors are set again
+            self.sgr.clear();
+            self.param(0);
+            let (fg, bg) = (self.fg, self.bg);
+            (self.fg, self.bg) = (Some(Color::Default), Some(Color::Default));
+            if let Some(fg) = fg {
+                self.set_fg(fg)?;
+            }
+            if let Some(bg) = bg {
+                self.set_bg(bg)?;
+            }
+            self.plain = true;
+        }
+        if attribute.is_underline() || attribute.is_reverse() || attribute.is_strikeout() {
+            self.plain = false;
+        }
+        Ok(())
+    }
+
+    fn reset_fg(&mut self) -> Result<(), Self::Error> {
+        self.set_fg(Color::Default)
+    }
+
+    fn reset_bg(&mut self) -> Result<(), Self::Error> {
+        self.set_bg(Color::Default)
+    }
+
+    fn reset_attribute(&mut self) -> Result<(), Self::Error> {
+        self.param(59);
+        Ok(())
+    }
+
+    fn write(&mut self, data: &str) -> Result<(), Self::Error> {
+        let mut chars = data.chars();
+        let single = match (chars.next(), chars.next()) {
+            (Some(ch), None) if ch.width() == Some(1) => Some(ch),
+            _ => None,
+        };
+
+        if let (Some(ch), Some(run), Some(target)) = (single, &mut self.run, &mut self.target)
+            && run.ch == ch
+            && run.end == *target
+            && self.sgr.is_empty()
+        {
+            run.count += 1;
+            run.end.x += 1;
+            target.x += 1;
+            return Ok(());
+        }
+
+        self.flush_pending()?;
+        match (single, self.target) {
+            (Some(ch), Some(mut end)) => {
+                end.x += 1;
+                self.target = Some(end);
+                self.run = Some(Run {
+                    ch,
+                    count: 1,
+                    end,
+                    plain: self.plain,
+                });
+            }
+            _ => {
+                self.out.write_all(data.as_bytes())?;
+                let width = data.width() as i32;
+                if let Some(cursor) = &mut self.cursor {
+                    cursor.x += width;
+                }
+                if let Some(target) = &mut self.target {
+                    target.x += width;
+                }
+                self.wrap_pending = true;
+            }
+        }
+        Ok(())
+    }
+
+    fn show_cursor(&mut self) -> Result<(), Self::Error> {
+        self.sync()?;
+        self.out.write_all(csi!("?25h"))
+    }
+
+    fn hide_cursor(&mut self) -> Result<(), Self::Error> {
+        self.flush_run()?;
+        self.out.write_all(csi!("?25l"))
+    }
+
+    fn set_cursor_shape(&mut self, shape: CursorShape, blinking: bool) -> Result<(), Self::Error> {
+        self.flush_run()?;
+        self.buf.clear();
+        self.buf.extend_from_slice(b"\x1b[");
+        push_u32(&mut self.buf, shape.style(blinking) as u32);
+        self.buf.extend_from_slice(b" q");
+        self.out.write_all(&self.buf)
+    }
+}
+
+/// `CUP`, leaving out the parameters that are already the default
+fn push_move_absolute(buf: &mut Vec<u8>, to: Position) {
+    buf.extend_from_slice(b"\x1b[");
+    if to.y > 0 || to.x > 0 {
+        push_u32(buf, to.y.max(0) as u32 + 1);
+    }
+    if to.x > 0 {
+        buf.push(b';');
+        push_u32(buf, to.x as u32 + 1);
+    }
+    buf.push(b'H');
+}
+
+fn push_move_relative(buf: &mut Vec<u8>, from: Position, to: Position, wrap_pending: bool) {
+    let dy = to.y - from.y;
+    if dy > 0 {
+        let dy = dy as u32;
+        // in raw mode a line feed only moves down
+        if dy as usize <= csi_len(dy) {
+            buf.extend(std::iter::repeat_n(b'\n', dy as usize));
+        } else {
+            push_csi(buf, dy, b'B');
+        }
+    } else if dy < 0 {
+        push_csi(buf, dy.unsigned_abs(), b'A');
+    }
+
+    // either from the start of the line, or from where the cursor is
+    let start_len = 1 + if to.x > 0 { csi_len(to.x as u32) } else { 0 };
+    let dx = to.x - from.x;
+    let step_len = match dx {
+        0 => 0,
+        -1 => 1,
+        dx => csi_len(dx.unsigned_abs()),
+    };
+
+    if wrap_pending || start_len < step_len {
+        buf.push(b'\r');
+        if to.x > 0 {
+            push_csi(buf, to.x as u32, b'C');
+        }
+        return;
+    }
+
+    match dx {
+        0 => {}
+        -1 => buf.push(b'\x08'),
+        dx if dx < 0 => push_csi(buf, dx.unsigned_abs(), b'D'),
+        dx => push_csi(buf, dx as u32, b'C'),
+    }
+}
+
+/// A `CSI` sequence with one parameter, which is left out when it's the default of 1
+fn push_csi(buf: &mut Vec<u8>, n: u32, end: u8) {
+    buf.extend_from_slice(b"\x1b[");
+    if n != 1 {
+        push_u32(buf, n);
+    }
+    buf.push(end);
+}
+
+/// How long [`push_csi`] makes the sequence
+const fn csi_len(n: u32) -> usize {
+    match n.checked_ilog10() {
+        _ if n == 1 => 3,
+        Some(digits) => 4 + digits as usize,
+        None => 4,
+    }
+}
+
+fn push_u32(buf: &mut Vec<u8>, mut n: u32) {
+    let mut digits = [0u8; 10];
+    let mut i = digits.len();
+    loop {
+        i -= 1;
+        digits[i] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            break;
+        }
+    }
+    buf.extend_from_slice(&digits[i..]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What's written between [`Rasterizer::begin`] and [`Rasterizer::end`]
+    fn encode(
+        mut out: StreamingRasterizer<Vec<u8>>,
+        draw: impl FnOnce(&mut StreamingRasterizer<Vec<u8>>) -> std::io::Result<()>,
+    ) -> String {
+        out.begin().unwrap();
+        draw(&mut out).unwrap();
+        out.end().unwrap();
+
+        let out = String::from_utf8(out.into_inner()).unwrap();
+        let out = out.strip_prefix("\x1b[?2026h\x1b[0m").unwrap();
+        out.strip_suffix("\x1b[?2026l").unwrap().to_string()
+    }
+
+    fn write_at(
+        out: &mut StreamingRasterizer<Vec<u8>>,
+        x: i32,
+        y: i32,
+        data: &str,
+    ) -> std::io::Result<()> {
+        out.move_to(Position::new(x, y))?;
+        out.write(data)
+    }
+
+    fn new() -> StreamingRasterizer<Vec<u8>> {
+        StreamingRasterizer::new(Vec::new())
+    }
+
+    #[test]
+    fn absolute_moves() {
+        assert_eq!(encode(new(), |out| write_at(out, 0, 0, "a")), "\x1b[Ha");
+        assert_eq!(encode(new(), |out| write_at(out, 0, 2, "a")), "\x1b[3Ha");
+        assert_eq!(encode(new(), |out| write_at(out, 5, 2, "a")), "\x1b[3;6Ha");
+
+        let out = new().with_origin(Position::new(0, 3));
+        assert_eq!(encode(out, |out| write_at(out, 1, 0, "a")), "\x1b[4;2Ha");
+    }
+
+    #[test]
+    fn relative_moves() {
+        let out = encode(new(), |out| {
+            write_at(out, 0, 0, "a")?;
+            write_at(out, 0, 1, "b")
+        });
+        assert_eq!(out, "\x1b[Ha\n\rb");
+
+        // the cursor could be past the last column, so it starts from the beginning of the line
+        let out = encode(new(), |out| {
+            write_at(out, 0, 0, "a")?;
+            write_at(out, 5, 0, "b")
+        });
+        assert_eq!(out, "\x1b[Ha\r\x1b[5Cb");
+
+        let out = encode(new(), |out| {
+            write_at(out, 0, 20, "a")?;
+            write_at(out, 0, 19, "b")
+        });
+        assert_eq!(out, "\x1b[21Ha\x1b[A\rb");
+
+        // unless the absolute move is shorter
+        let out = encode(new(), |out| {
+            write_at(out, 0, 3, "a")?;
+            write_at(out, 1, 1, "b")
+        });
+        assert_eq!(out, "\x1b[4Ha\x1b[2;2Hb");
+
+        // a move to where the cursor already is isn't sent
+        let out = encode(new(), |out| {
+            write_at(out, 0, 0, "ab")?;
+            write_at(out, 2, 0, "c")
+        });
+        assert_eq!(out, "\x1b[Habc");
+    }
+
+    #[test]
+    fn repeats() {
+        let run = |out: &mut StreamingRasterizer<Vec<u8>>, ch: &str, n: i32| {
+            (0..n).try_for_each(|x| write_at(out, x, 0, ch))
+        };
+
+        assert_eq!(encode(new(), |out| run(out, "x", 3)), "\x1b[Hxxx");
+        assert_eq!(encode(new(), |out| run(out, "x", 10)), "\x1b[Hx\x1b[9b");
+
+        let out = encode(new().repeat(false), |out| run(out, "x", 10));
+        assert_eq!(out, "\x1b[Hxxxxxxxxxx");
+
+        // a color change ends the run
+        let out = encode(new(), |out| {
+            run(out, "x", 10)?;
+            out.set_fg(Color::Named(IndexedColor(1)))?;
+            write_at(out, 10, 0, "x")
+        });
+        assert_eq!(out, "\x1b[Hx\x1b[9b\x1b[38;5;1mx");
+    }
+
+    #[test]
+    fn erased_blanks() {
+        // blanks at the end of a run are erased, and the cursor doesn't move
+        let out = encode(new(), |out| {
+            (0..10).try_for_each(|x| write_at(out, x, 0, " "))?;
+            write_at(out, 0, 1, "b")
+        });
+        assert_eq!(out, "\x1b[H\x1b[10X\nb");
+
+        // but blanks followed by more writes are repeated
+        let out = encode(new(), |out| {
+            (0..10).try_for_each(|x| write_at(out, x, 0, " "))?;
+            write_at(out, 10, 0, "b")
+        });
+        assert_eq!(out, "\x1b[H \x1b[9bb");
+
+        // an underline shows up on blanks, so they can't be erased
+        let out = encode(new(), |out| {
+            out.set_attribute(Attributes::UNDERLINE)?;
+            (0..10).try_for_each(|x| write_at(out, x, 0, " "))?;
+            write_at(out, 0, 1, "b")
+        });
+        assert_eq!(out, "\x1b[H\x1b[4m \x1b[9b\n\rb");
+    }
+
+    #[test]
+    fn combined_sgr() {
+        let out = encode(new(), |out| {
+            out.set_fg(Color::Named(IndexedColor(1)))?;
+            out.set_bg(Color::Rgba(Rgba(1, 2, 3, 255)))?;
+            out.set_attribute(Attributes::BOLD)?;
+            write_at(out, 0, 0, "a")
+        });
+        assert_eq!(out, "\x1b[H\x1b[38;5;1;48;2;1;2;3;1ma");
+
+        // unchanged colors aren't sent again
+        let out = encode(new(), |out| {
+            out.set_fg(Color::Named(IndexedColor(1)))?;
+            write_at(out, 0, 0, "a")?;
+            out.set_fg(Color::Named(IndexedColor(1)))?;
+            out.set_bg(Color::Default)?;
+            write_at(out, 1, 0, "b")
+        });
+        assert_eq!(out, "\x1b[H\x1b[38;5;1mab");
+    }
+
+    #[test]
+    fn reset_keeps_colors() {
+        let out = encode(new(), |out| {
+            out.set_fg(Color::Named(IndexedColor(1)))?;
+            out.set_attribute(Attributes::BOLD)?;
+            out.set_attribute(Attributes::RESET)?;
+            write_at(out, 0, 0, "a")
+        });
+        assert_eq!(out, "\x1b[H\x1b[0;38;5;1ma");
+    }
+
+    #[test]
+    fn scroll() {
+        let out = encode(new(), |out| {
+            out.scroll(2..5, 1)?;
+            write_at(out, 0, 4, "a")
+        });
+        assert_eq!(out, "\x1b[3;5r\x1b[S\x1b[r\x1b[5Ha");
+
+        // the margins are on the screen, and the cursor is left at its top left
+        let out = encode(new().with_origin(Position::new(0, 10)), |out| {
+            out.scroll(0..4, -2)?;
+            write_at(out, 0, 0, "a")
+        });
+        assert_eq!(out, "\x1b[11;14r\x1b[2T\x1b[r\x1b[11Ha");
+    }
+
+    #[test]
+    fn wide_characters() {
+        let out = encode(new(), |out| {
+            write_at(out, 0, 0, "你")?;
+            write_at(out, 2, 0, "x")
+        });
+        assert_eq!(out, "\x1b[H你x");
+    }
+}
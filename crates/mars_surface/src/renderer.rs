@@ -1,9 +1,9 @@
-use std::ops::RangeInclusive;
+use std::ops::{Range, RangeInclusive};
 
 mod basic_renderer;
 pub use basic_renderer::BasicRenderer;
 
-use crate::{Color, Drawable, Pixel, Rasterizer};
+use crate::{Color, Cursor, Drawable, Pixel, Rasterizer};
 use mars_math::{Axis, Position, Size};
 
 #[derive(Copy, Clone, Default, Debug, PartialEq)]
@@ -97,6 +97,19 @@ pub trait Renderer: RendererSetup + Placer {
         render.draw(self, Position::ZERO, blend);
     }
 
+    /// Places the terminal's cursor for the next frame
+    ///
+    /// Without a cursor, one shown by the previous frame is hidden again and the cursor is parked at the top left.
+    /// Its visibility is otherwise left alone, so it stays visible unless the terminal was set up to hide it
+    fn set_cursor(&mut self, cursor: impl Into<Option<Cursor>>) {
+        _ = cursor
+    }
+
+    /// Hints that what's in `rows` moved up by `lines` since the last frame, or down if it's negative
+    fn scroll(&mut self, rows: Range<u32>, lines: i32) {
+        _ = (rows, lines)
+    }
+
     fn render<R: Rasterizer>(&mut self, rasterizer: R) -> Result<(), R::Error>;
 }
 
@@ -1,24 +1,48 @@
+use std::{
+    hash::{Hash as _, Hasher as _},
+    ops::Range,
+};
+
 use mars_math::{Position, Size};
 
-use crate::{BlendMode, Color, Pixel, Rasterizer, Renderer, RendererSetup, ResizeMode, Surface};
+use crate::{
+    BlendMode, Color, Cursor, Pixel, Rasterizer, Renderer, RendererSetup, ResizeMode, Surface,
+    pixel::PixelData,
+};
 
 use super::Placer;
 
 #[derive(Debug)]
 pub struct BasicRenderer {
     surface: Surface<Pixel>,
+    // what the terminal shows, as of the last frame
+    front: Surface<Pixel>,
+    // which rows of `front` are really on the terminal
+    painted: Vec<bool>,
+    // which rows of `front` are nothing but blanks
+    blank: Vec<bool>,
+    scrolls: Vec<(Range<u32>, i32)>,
     size: Size,
     default_fg: Color,
     default_bg: Color,
+    cursor: Option<Cursor>,
+    // the cursor as it was left by the previous frame
+    shown_cursor: Option<Cursor>,
 }
 
 impl BasicRenderer {
     pub fn new(size: Size) -> Self {
         Self {
             surface: Surface::new(size, Pixel::empty()),
+            front: Surface::new(size, Pixel::empty()),
+            painted: vec![false; size.height as usize],
+            blank: vec![false; size.height as usize],
+            scrolls: Vec::new(),
             size,
             default_fg: Color::default(),
             default_bg: Color::default(),
+            cursor: None,
+            shown_cursor: None,
         }
     }
 
@@ -33,31 +57,85 @@ impl BasicRenderer {
     }
 
     pub fn set_default_fg(&mut self, default_fg: impl Into<Color>) {
-        self.default_fg = default_fg.into();
+        let default_fg = default_fg.into();
+        if std::mem::replace(&mut self.default_fg, default_fg) != default_fg {
+            self.invalidate();
+        }
     }
 
     pub fn set_default_bg(&mut self, default_bg: impl Into<Color>) {
-        self.default_bg = default_bg.into();
+        let default_bg = default_bg.into();
+        if std::mem::replace(&mut self.default_bg, default_bg) != default_bg {
+            self.invalidate();
+        }
     }
 
-    pub fn resize(&mut self, size: Size, _mode: ResizeMode) {
-        if std::mem::replace(&mut self.size, size) == size {
-            return;
+    /// The terminal is assumed to be cleared, so the next frame is drawn in full
+    ///
+    /// With [`ResizeMode::Keep`] that frame starts out with what was on the terminal,
+    /// along with anything drawn since the last render
+    pub fn resize(&mut self, size: Size, mode: ResizeMode) {
+        if std::mem::replace(&mut self.size, size) != size {
+            // rendering hands the surface over to the terminal, so what's kept comes from `front`
+            let undrawn = (0..self.surface.size().height)
+                .map(|y| self.surface.row_damage(y).is_none())
+                .collect::<Vec<_>>();
+
+            self.surface.resize(size, mode);
+            self.front.resize(size, mode);
+
+            if mode == ResizeMode::Keep {
+                for y in 0..size.height.min(undrawn.len() as u32) {
+                    if !undrawn[y as usize] || !self.painted[y as usize] {
+                        continue;
+                    }
+                    if let Some(row) = self.front.row(y) {
+                        self.surface.clone_row(Position::new(0, y as i32), row);
+                    }
+                }
+            }
         }
-        self.surface.resize(size, ResizeMode::Discard);
+        self.invalidate();
+    }
+
+    /// Forgets what's on the terminal, so the next frame is drawn in full, e.g. after something else drew over it
+    pub fn invalidate(&mut self) {
+        self.painted.clear();
+        self.painted.resize(self.size.height as usize, false);
+        self.blank.clear();
+        self.blank.resize(self.size.height as usize, false);
+        self.scrolls.clear();
+    }
+
+    /// Tells the renderer that what's in `rows` moved up by `lines` since the last frame, or down if it's negative
+    ///
+    /// The terminal is scrolled to match, so only the rows that scrolled in are drawn.
+    /// Without this a scroll of a band of rows is still detected, if it's by at most half of the screen
+    pub fn scroll(&mut self, rows: Range<u32>, lines: i32) {
+        self.scrolls.push((rows, lines));
     }
 
     pub fn size(&self) -> Size {
         self.size
     }
 
+    /// What has been drawn since the last render
+    pub const fn surface(&self) -> &Surface<Pixel> {
+        &self.surface
+    }
+
+    /// Places the terminal's cursor for the next frame, see [`Renderer::set_cursor`]
+    pub fn set_cursor(&mut self, cursor: impl Into<Option<Cursor>>) {
+        self.cursor = cursor.into();
+    }
+
     // TODO don't ignore the blend mode
     pub fn put(&mut self, pos: Position, pixel: Pixel, _blend: BlendMode) {
         let Some(pos) = pos.to_unsigned_checked() else {
             return;
         };
 
-        if pos.x > self.size.width || pos.y >= self.size.height {
+        if pos.x >= self.size.width || pos.y >= self.size.height {
             return;
         }
 
@@ -76,7 +154,38 @@ impl BasicRenderer {
 
         rasterizer.begin()?;
 
+        // keep the cursor from flickering across the screen while drawing
+        if self.shown_cursor.is_some() {
+            rasterizer.hide_cursor()?;
+        }
+
+        let mut scrolls = std::mem::take(&mut self.scrolls);
+        if scrolls.is_empty() && self.surface.is_damaged() {
+            scrolls.extend(self.detect_scroll());
+        }
+        for (rows, lines) in scrolls.drain(..) {
+            self.scroll_front(&mut rasterizer, rows, lines)?;
+        }
+        self.scrolls = scrolls;
+
         for y in 0..self.size.height {
+            let row = y as usize;
+            // nothing was drawn into a row that still holds the blanks it was reset to
+            let damaged = self.surface.row_damage(y).is_some();
+            if !damaged && self.painted[row] && self.blank[row] {
+                continue;
+            }
+
+            // rows that are already on the terminal are skipped
+            if self.painted[row] && self.surface.row(y) == self.front.row(y) {
+                for x in 0..self.size.width {
+                    self.surface[(x, y)] = replacement.clone();
+                }
+                continue;
+            }
+
+            let mut blank = true;
+
             for x in 0..self.size.width {
                 let pos = Position::new(x, y);
 
@@ -94,21 +203,147 @@ impl BasicRenderer {
 
                 // TODO attributes
 
-                match pixel.data {
-                    crate::pixel::PixelData::Char(ch) => {
+                match &pixel.data {
+                    PixelData::Char(ch) => {
                         let s = ch.encode_utf8(&mut data);
                         rasterizer.write(s)?;
                     }
-                    crate::pixel::PixelData::Str(s) => {
-                        rasterizer.write(&s)?;
+                    PixelData::Str(s) => {
+                        rasterizer.write(s)?;
                     }
                 };
+                blank &= pixel == replacement;
+                self.front[pos] = pixel;
+            }
+            self.painted[row] = true;
+            self.blank[row] = blank;
+        }
+        self.surface.clear_damage();
+
+        match self.cursor.take() {
+            Some(cursor) => {
+                rasterizer.move_to(cursor.pos)?;
+                let previous = self.shown_cursor.map(|c| (c.shape, c.blinking));
+                if previous != Some((cursor.shape, cursor.blinking)) {
+                    rasterizer.set_cursor_shape(cursor.shape, cursor.blinking)?;
+                }
+                rasterizer.show_cursor()?;
+                self.shown_cursor = Some(cursor);
+            }
+            None => {
+                rasterizer.move_to(Position::ZERO)?;
+                self.shown_cursor = None;
             }
         }
 
-        rasterizer.move_to(Position::ZERO)?;
         rasterizer.end()
     }
+
+    /// Scrolls the terminal and what we know of it
+    fn scroll_front<R: Rasterizer>(
+        &mut self,
+        rasterizer: &mut R,
+        rows: Range<u32>,
+        lines: i32,
+    ) -> Result<(), R::Error> {
+        let rows = rows.start.min(self.size.height)..rows.end.min(self.size.height);
+        if rows.is_empty() || lines == 0 {
+            return Ok(());
+        }
+
+        let cells = rows.start as usize..rows.end as usize;
+        let painted = &mut self.painted[cells.clone()];
+        let n = lines.unsigned_abs() as usize;
+        if n >= painted.len() {
+            painted.fill(false);
+            return Ok(());
+        }
+
+        // without scrolling, the rows are drawn again
+        if !rasterizer.scroll(rows.clone(), lines)? {
+            painted.fill(false);
+            return Ok(());
+        }
+        self.front.rotate_rows(rows, lines);
+        let blank = &mut self.blank[cells];
+        if lines > 0 {
+            painted.rotate_left(n);
+            blank.rotate_left(n);
+            let len = painted.len();
+            painted[len - n..].fill(false);
+        } else {
+            painted.rotate_right(n);
+            blank.rotate_right(n);
+            painted[..n].fill(false);
+        }
+        Ok(())
+    }
+
+    /// Finds the band of rows that moved the furthest, if scrolling it saves drawing a few rows
+    fn detect_scroll(&self) -> Option<(Range<u32>, i32)> {
+        // rows are compared by their hashes, so this doesn't compare every pair of rows cell by cell
+        let hash = |row: Option<&[Pixel]>| {
+            let mut hasher = std::hash::DefaultHasher::new();
+            row.hash(&mut hasher);
+            hasher.finish()
+        };
+
+        let height = self.size.height;
+        let drawn = (0..height)
+            .map(|y| hash(self.surface.row(y)))
+            .collect::<Vec<_>>();
+        let shown = (0..height)
+            .map(|y| self.painted[y as usize].then(|| hash(self.front.row(y))))
+            .collect::<Vec<_>>();
+        let is_shown = |y: u32, from: u32| shown[from as usize] == Some(drawn[y as usize]);
+
+        // a scroll costs about as much as drawing a short row
+        const MIN_GAIN: usize = 2;
+
+        let unchanged = (0..height).map(|y| is_shown(y, y)).collect::<Vec<_>>();
+        if unchanged.iter().filter(|&&same| !same).count() <= MIN_GAIN {
+            return None;
+        }
+
+        // the band that moved, its first and last row, and by how much
+        let mut best = None;
+        let mut best_gain = MIN_GAIN;
+        for n in 1..=height as i32 / 2 {
+            for lines in [n, -n] {
+                let (mut start, mut gain) = (0, 0);
+                for y in 0..height {
+                    let from = y as i32 + lines;
+                    if !(0..height as i32).contains(&from) || !is_shown(y, from as u32) {
+                        (start, gain) = (y + 1, 0);
+                        continue;
+                    }
+
+                    gain += !unchanged[y as usize] as usize;
+                    if gain > best_gain {
+                        best_gain = gain;
+                        best = Some((start, y, lines));
+                    }
+                }
+            }
+        }
+
+        // hashes can collide, so make sure the band really is on the terminal
+        let (start, end, lines) = best?;
+        let moved = (start..=end).all(|y| {
+            let from = (y as i32 + lines) as u32;
+            self.surface.row(y) == self.front.row(from)
+        });
+        if !moved {
+            return None;
+        }
+
+        // the band plus the rows it scrolled out of
+        let n = lines.unsigned_abs();
+        Some(match lines > 0 {
+            true => (start..end + 1 + n, lines),
+            false => (start - n..end + 1, lines),
+        })
+    }
 }
 
 #[derive(Default)]
@@ -175,6 +410,14 @@ impl Renderer for BasicRenderer {
         );
     }
 
+    fn set_cursor(&mut self, cursor: impl Into<Option<Cursor>>) {
+        Self::set_cursor(self, cursor)
+    }
+
+    fn scroll(&mut self, rows: Range<u32>, lines: i32) {
+        Self::scroll(self, rows, lines)
+    }
+
     fn render<R: Rasterizer>(&mut self, rasterizer: R) -> Result<(), R::Error> {
         Self::render(self, rasterizer)
     }
@@ -193,3 +436,206 @@ impl RendererSetup for BasicRenderer {
         Self::set_default_bg(self, default_bg)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Attributes, DebugOp, DebugRasterizer, Snapshot};
+
+    fn draw(renderer: &mut BasicRenderer, pos: Position, text: &str) {
+        for (i, ch) in text.chars().enumerate() {
+            renderer.put(
+                pos + Position::new(i as i32, 0),
+                Pixel::new(ch),
+                BlendMode::Replace,
+            );
+        }
+    }
+
+    fn draw_rows(renderer: &mut BasicRenderer, first: u32) {
+        for y in 0..renderer.size().height {
+            draw(
+                renderer,
+                Position::new(0, y as i32),
+                &format!("row{}", first + y),
+            );
+        }
+    }
+
+    fn scrolls(out: &DebugRasterizer) -> Vec<(Range<u32>, i32)> {
+        out.ops()
+            .iter()
+            .filter_map(|op| match op {
+                DebugOp::Scroll { rows, lines } => Some((rows.clone(), *lines)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detect_scroll() {
+        let mut renderer = BasicRenderer::new(Size::new(6, 8));
+        draw_rows(&mut renderer, 0);
+        let Ok(()) = renderer.render(DebugRasterizer::new());
+
+        // everything moved up by two, only the two rows scrolled in are drawn
+        draw_rows(&mut renderer, 2);
+        let mut out = DebugRasterizer::new();
+        let Ok(()) = renderer.render(&mut out);
+        assert_eq!(scrolls(&out), [(0..8, 2)]);
+        assert_eq!(out.written().collect::<String>(), "row8  row9  ");
+
+        // and back down by one
+        draw_rows(&mut renderer, 1);
+        let mut out = DebugRasterizer::new();
+        let Ok(()) = renderer.render(&mut out);
+        assert_eq!(scrolls(&out), [(0..8, -1)]);
+        assert_eq!(out.written().collect::<String>(), "row1  ");
+    }
+
+    /// Only implements what it has to, so it can't scroll
+    #[derive(Default)]
+    struct Plain(String);
+
+    impl Rasterizer for Plain {
+        type Error = std::convert::Infallible;
+
+        fn begin(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn end(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn clear(&mut self, _: Position, _: Size) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn clear_screen(&mut self, _: Color, _: Size) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn move_to(&mut self, _: Position) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn default_fg(&mut self, _: Color) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn default_bg(&mut self, _: Color) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn set_fg(&mut self, _: Color) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn set_bg(&mut self, _: Color) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn set_attribute(&mut self, _: Attributes) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn reset_fg(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn reset_bg(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn reset_attribute(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn write(&mut self, data: &str) -> Result<(), Self::Error> {
+            self.0.push_str(data);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn repaint_without_scrolling() {
+        let mut renderer = BasicRenderer::new(Size::new(6, 4));
+        draw_rows(&mut renderer, 0);
+        let Ok(()) = renderer.render(Plain::default());
+
+        // the rows that would have scrolled are drawn again
+        draw_rows(&mut renderer, 2);
+        let mut out = Plain::default();
+        let Ok(()) = renderer.render(&mut out);
+        assert_eq!(out.0, "row2  row3  row4  row5  ");
+
+        draw_rows(&mut renderer, 2);
+        let mut out = Plain::default();
+        let Ok(()) = renderer.render(&mut out);
+        assert_eq!(out.0, "");
+    }
+
+    #[test]
+    fn small_changes_dont_scroll() {
+        let mut renderer = BasicRenderer::new(Size::new(6, 8));
+        draw_rows(&mut renderer, 0);
+        let Ok(()) = renderer.render(DebugRasterizer::new());
+
+        draw_rows(&mut renderer, 0);
+        draw(&mut renderer, Position::new(0, 3), "x");
+        let mut out = DebugRasterizer::new();
+        let Ok(()) = renderer.render(&mut out);
+        assert!(scrolls(&out).is_empty());
+        assert_eq!(out.written().collect::<String>(), "xow3  ");
+    }
+
+    #[test]
+    fn untouched_rows_are_skipped() {
+        let mut renderer = BasicRenderer::new(Size::new(4, 3));
+        draw(&mut renderer, Position::new(0, 1), "ab");
+        let mut out = DebugRasterizer::new();
+        let Ok(()) = renderer.render(&mut out);
+        assert_eq!(out.stats().cells_written, 12);
+
+        // only the row that was drawn into is looked at, and it's unchanged
+        draw(&mut renderer, Position::new(0, 1), "ab");
+        let mut out = DebugRasterizer::new();
+        let Ok(()) = renderer.render(&mut out);
+        assert_eq!(out.stats().cells_written, 0);
+
+        // a row that isn't drawn into again is cleared, blank rows that stay blank are skipped
+        draw(&mut renderer, Position::new(2, 2), "c");
+        let mut out = DebugRasterizer::new();
+        let Ok(()) = renderer.render(&mut out);
+        assert_eq!(out.written().collect::<String>(), "      c ");
+    }
+
+    #[test]
+    fn resize_keep() {
+        let mut renderer = BasicRenderer::new(Size::new(6, 2));
+        draw(&mut renderer, Position::new(0, 0), "hello");
+        draw(&mut renderer, Position::new(1, 1), "world");
+        let Ok(()) = renderer.render(DebugRasterizer::new());
+
+        // drawn after the render, so it's never been on the terminal
+        draw(&mut renderer, Position::new(0, 1), "w");
+
+        renderer.resize(Size::new(4, 3), ResizeMode::Keep);
+        Snapshot::new(renderer.surface()).assert_eq("hell\nw\n\n");
+
+        let mut out = DebugRasterizer::new();
+        let Ok(()) = renderer.render(&mut out);
+        assert_eq!(out.written().collect::<String>(), "hellw       ");
+    }
+
+    #[test]
+    fn resize_discard() {
+        let mut renderer = BasicRenderer::new(Size::new(6, 2));
+        draw(&mut renderer, Position::new(0, 0), "hello");
+        let Ok(()) = renderer.render(DebugRasterizer::new());
+
+        renderer.resize(Size::new(4, 3), ResizeMode::Discard);
+        Snapshot::new(renderer.surface()).assert_eq("\n\n\n");
+    }
+}
@@ -0,0 +1,280 @@
+use std::fmt::Write as _;
+
+use crate::{Attributes, Color, IndexedColor, Pixel, Rgba, Surface, pixel::PixelData};
+
+/// Setting this environment variable makes [`Snapshot::assert_matches_file`] overwrite the golden files
+pub const UPDATE_SNAPSHOTS: &str = "MARS_UPDATE_SNAPSHOTS";
+
+/// A plain text picture of a [`Surface<Pixel>`], for comparing against golden files in tests
+///
+/// Each row of the surface is a line of text, with its trailing whitespace trimmed.
+/// [`Snapshot::with_styles`] also lists the runs of styled cells below the text, one per line, like
+/// `1 4..9 fg=#FF0000FF bg=default attrs=bold`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    text: String,
+}
+
+impl Snapshot {
+    pub fn new(surface: &Surface<Pixel>) -> Self {
+        let mut text = String::new();
+        for (_, row) in surface.rows() {
+            let start = text.len();
+            for pixel in row {
+                match &pixel.data {
+                    PixelData::Char(ch) => text.push(*ch),
+                    PixelData::Str(s) => text.push_str(s),
+                }
+            }
+            text.truncate(start + text[start..].trim_end().len());
+            text.push('\n');
+        }
+        Self { text }
+    }
+
+    /// Includes the colors and attributes of every cell that isn't unstyled
+    pub fn with_styles(surface: &Surface<Pixel>) -> Self {
+        let mut this = Self::new(surface);
+        this.text.push_str("---\n");
+
+        for (y, row) in surface.rows() {
+            let mut x = 0;
+            for run in row.chunk_by(|l, r| style_of(l) == style_of(r)) {
+                let (start, end) = (x, x + run.len());
+                x = end;
+
+                let (fg, bg, attrs) = style_of(&run[0]);
+                if (fg, bg, attrs) == (Color::Default, Color::Default, None) {
+                    continue;
+                }
+
+                _ = write!(
+                    &mut this.text,
+                    "{y} {start}..{end} fg={} bg={}",
+                    ColorName(fg),
+                    ColorName(bg)
+                );
+                if let Some(attrs) = attrs {
+                    _ = write!(&mut this.text, " attrs={}", AttributeNames(attrs));
+                }
+                this.text.push('\n');
+            }
+        }
+        this
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// A line diff against the expected snapshot, or `None` if they're the same
+    pub fn diff(&self, expected: &str) -> Option<String> {
+        if self.text == expected {
+            return None;
+        }
+        Some(line_diff(expected, &self.text))
+    }
+
+    #[track_caller]
+    pub fn assert_eq(&self, expected: &str) {
+        if let Some(diff) = self.diff(expected) {
+            panic!("snapshot doesn't match (- expected, + actual):\n{diff}");
+        }
+    }
+
+    /// Compares against a golden file
+    ///
+    /// The file is written if it doesn't exist yet, or if [`UPDATE_SNAPSHOTS`] is set
+    #[track_caller]
+    pub fn assert_matches_file(&self, path: impl AsRef<std::path::Path>) {
+        let path = path.as_ref();
+        let update = std::env::var_os(UPDATE_SNAPSHOTS).is_some();
+
+        match std::fs::read_to_string(path) {
+            Ok(expected) if !update => {
+                if let Some(diff) = self.diff(&expected) {
+                    panic!(
+                        "snapshot doesn't match {} (- expected, + actual):\n{diff}\n\
+                        set {UPDATE_SNAPSHOTS}=1 to update it",
+                        path.display()
+                    );
+                }
+            }
+            Err(err) if !update && err.kind() != std::io::ErrorKind::NotFound => {
+                panic!("cannot read {}: {err}", path.display())
+            }
+            _ => {
+                if let Some(parent) = path.parent() {
+                    _ = std::fs::create_dir_all(parent);
+                }
+                if let Err(err) = std::fs::write(path, &self.text) {
+                    panic!("cannot write {}: {err}", path.display())
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.text.fmt(f)
+    }
+}
+
+fn style_of(pixel: &Pixel) -> (Color, Color, Option<Attributes>) {
+    (pixel.foreground, pixel.background, pixel.attributes)
+}
+
+struct ColorName(Color);
+
+impl std::fmt::Display for ColorName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Color::Named(IndexedColor(index)) => write!(f, "{index}"),
+            Color::Rgba(Rgba(r, g, b, a)) => write!(f, "#{r:02X}{g:02X}{b:02X}{a:02X}"),
+            Color::Default => f.write_str("default"),
+        }
+    }
+}
+
+struct AttributeNames(Attributes);
+
+impl std::fmt::Display for AttributeNames {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let attrs = self.0;
+        if attrs == Attributes::RESET {
+            return f.write_str("reset");
+        }
+
+        let names = [
+            (attrs.is_bold(), "bold"),
+            (attrs.is_faint(), "faint"),
+            (attrs.is_italic(), "italic"),
+            (attrs.is_underline(), "underline"),
+            (attrs.is_blink(), "blink"),
+            (attrs.is_reverse(), "reverse"),
+            (attrs.is_strikeout(), "strikeout"),
+        ];
+        let mut names = names.iter().filter(|(set, _)| *set).map(|(_, name)| name);
+        if let Some(first) = names.next() {
+            f.write_str(first)?;
+        }
+        for name in names {
+            write!(f, ",{name}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A longest common subsequence diff of the lines
+fn line_diff(expected: &str, actual: &str) -> String {
+    let old = expected.lines().collect::<Vec<_>>();
+    let new = actual.lines().collect::<Vec<_>>();
+
+    // lengths of the common subsequences of the suffixes
+    let mut table = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            table[i][j] = if old[i] == new[j] {
+                table[i + 1][j + 1] + 1
+            } else {
+                table[i + 1][j].max(table[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            _ = writeln!(&mut out, "  {}", old[i]);
+            (i, j) = (i + 1, j + 1);
+        } else if i < old.len() && (j == new.len() || table[i + 1][j] >= table[i][j + 1]) {
+            _ = writeln!(&mut out, "- {}", old[i]);
+            i += 1;
+        } else {
+            _ = writeln!(&mut out, "+ {}", new[j]);
+            j += 1;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mars_math::{Position, Size};
+
+    fn surface(lines: &[&str]) -> Surface<Pixel> {
+        let width = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+        let size = Size::new(width as u32, lines.len() as u32);
+        let mut surface = Surface::new(size, Pixel::new(' '));
+        for (y, line) in lines.iter().enumerate() {
+            for (x, ch) in line.chars().enumerate() {
+                surface.set(Position::new(x as i32, y as i32), Pixel::new(ch));
+            }
+        }
+        surface
+    }
+
+    #[test]
+    fn text() {
+        let snapshot = Snapshot::new(&surface(&["hi  ", "    ", " there"]));
+        assert_eq!(snapshot.as_str(), "hi\n\n there\n");
+        assert_eq!(snapshot.diff("hi\n\n there\n"), None);
+    }
+
+    #[test]
+    fn with_styles() {
+        let mut surface = surface(&["abcdef", "ghijkl"]);
+        for x in 1..3 {
+            let pos = Position::new(x, 0);
+            surface.set(pos, Pixel::new('x').fg(Rgba(255, 0, 0, 255)));
+        }
+        let mut pixel = Pixel::new('y').bg(IndexedColor(4));
+        pixel.set_attribute(Attributes::BOLD | Attributes::UNDERLINE);
+        surface.set(Position::new(5, 1), pixel);
+
+        let snapshot = Snapshot::with_styles(&surface);
+        let expected = "\
+axxdef
+ghijky
+---
+0 1..3 fg=#FF0000FF bg=default
+1 5..6 fg=default bg=4 attrs=bold,underline
+";
+        snapshot.assert_eq(expected);
+    }
+
+    #[test]
+    fn line_diff() {
+        let diff = super::line_diff("a\nb\nc\n", "a\nx\nc\nd\n");
+        assert_eq!(diff, "  a\n- b\n+ x\n  c\n+ d\n");
+
+        let snapshot = Snapshot::new(&surface(&["one", "two"]));
+        assert_eq!(snapshot.diff("one\n").unwrap(), "  one\n+ two\n");
+    }
+
+    #[test]
+    fn assert_matches_file() {
+        let dir = std::env::temp_dir().join(format!("mars_snapshot_{}", std::process::id()));
+        let path = dir.join("nested").join("golden.txt");
+        _ = std::fs::remove_dir_all(&dir);
+
+        // a missing golden file is written
+        let snapshot = Snapshot::new(&surface(&["golden"]));
+        snapshot.assert_matches_file(&path);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "golden\n");
+        snapshot.assert_matches_file(&path);
+
+        let other = Snapshot::new(&surface(&["silver"]));
+        let err = std::panic::catch_unwind(|| other.assert_matches_file(&path)).unwrap_err();
+        let message = err.downcast_ref::<String>().unwrap();
+        assert!(message.contains("- golden\n+ silver\n"), "{message}");
+        assert!(message.contains(UPDATE_SNAPSHOTS), "{message}");
+        // a mismatch leaves the golden file alone
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "golden\n");
+
+        _ = std::fs::remove_dir_all(&dir);
+    }
+}
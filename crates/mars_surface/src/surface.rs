@@ -1,4 +1,6 @@
-use mars_math::{Position, Size};
+use std::ops::Range;
+
+use mars_math::{Delta, Position, Size};
 
 #[derive(Debug, Clone)]
 pub struct Surface<T> {
@@ -6,6 +8,8 @@ pub struct Surface<T> {
     size: Size,
     default: T,
     pixels: Vec<T>,
+    // the columns of each row written to since the damage was last cleared
+    damage: Vec<Range<u32>>,
 }
 
 impl<T> Surface<T> {
@@ -18,6 +22,7 @@ impl<T> Surface<T> {
             size,
             default: default.clone(),
             pixels: vec![default; size.area() as usize],
+            damage: vec![0..size.width; size.height as usize],
         }
     }
 
@@ -38,6 +43,7 @@ impl<T> Surface<T> {
         T: Clone,
     {
         self.pixels.fill(value);
+        self.damage_all();
     }
 
     pub fn resize(&mut self, size: Size, mode: ResizeMode)
@@ -52,7 +58,7 @@ impl<T> Surface<T> {
                 for y in 0..min.height {
                     let (x0, x1) = (
                         y as usize * self.size.width as usize,
-                        y as usize * min.width as usize,
+                        y as usize * size.width as usize,
                     );
                     let (y0, y1) = (x0 + min.width as usize, x1 + min.width as usize);
                     new[x1..y1].clone_from_slice(&self.pixels[x0..y0]);
@@ -65,6 +71,61 @@ impl<T> Surface<T> {
             }
         }
         self.size = size;
+        self.damage_all();
+    }
+
+    /// Which columns of each row were written to since [`Surface::clear_damage`], for the rows that were
+    ///
+    /// Anything that could have written counts, like [`Surface::get_mut`], even if the value stayed the same
+    pub fn damage(&self) -> impl Iterator<Item = (u32, Range<u32>)> + '_ {
+        self.damage
+            .iter()
+            .enumerate()
+            .filter(|(_, cols)| !cols.is_empty())
+            .map(|(y, cols)| (y as u32, cols.clone()))
+    }
+
+    pub fn row_damage(&self, y: u32) -> Option<Range<u32>> {
+        self.damage
+            .get(y as usize)
+            .filter(|cols| !cols.is_empty())
+            .cloned()
+    }
+
+    pub fn is_damaged(&self) -> bool {
+        self.damage.iter().any(|cols| !cols.is_empty())
+    }
+
+    pub fn clear_damage(&mut self) {
+        self.damage.fill(0..0);
+    }
+
+    /// Marks everything as written to
+    pub fn damage_all(&mut self) {
+        self.damage.clear();
+        self.damage
+            .resize(self.size.height as usize, 0..self.size.width);
+    }
+
+    /// Marks a span of cells, by their index, as written to
+    fn touch(&mut self, cells: Range<usize>) {
+        let width = self.size.width as usize;
+        if cells.is_empty() || width == 0 {
+            return;
+        }
+
+        for y in cells.start / width..=(cells.end - 1) / width {
+            let Some(damage) = self.damage.get_mut(y) else {
+                return;
+            };
+            let row = y * width;
+            let start = (cells.start.max(row) - row) as u32;
+            let end = (cells.end.min(row + width) - row) as u32;
+            *damage = match damage.start < damage.end {
+                true => damage.start.min(start)..damage.end.max(end),
+                false => start..end,
+            };
+        }
     }
 
     #[track_caller]
@@ -80,19 +141,45 @@ impl<T> Surface<T> {
         self.size
     }
 
+    /// The index of `pos` after the offset, if it's inside of the surface
     #[inline(always)]
+    fn checked_index(&self, pos: Position) -> Option<usize> {
+        let x = u32::try_from(pos.x.checked_add(self.pos.x)?).ok()?;
+        let y = u32::try_from(pos.y.checked_add(self.pos.y)?).ok()?;
+        if x >= self.size.width || y >= self.size.height {
+            return None;
+        }
+        Some(self.index(x, y))
+    }
+
+    /// Like [`Surface::checked_index`], for the `Index` impls
     #[track_caller]
+    #[inline(always)]
+    fn expect_index(&self, pos: Position) -> usize {
+        match self.checked_index(pos) {
+            Some(index) => index,
+            None => panic!(
+                "{pos:?} is outside of the surface, its size is {:?} and its offset is {:?}",
+                self.size, self.pos
+            ),
+        }
+    }
+
+    #[inline(always)]
+    const fn index(&self, x: u32, y: u32) -> usize {
+        y as usize * self.size.width as usize + x as usize
+    }
+
+    #[inline(always)]
     pub fn get(&self, pos: Position) -> Option<&T> {
-        // FIXME check over/underflow
-        let index = Self::pos_of(self.size.width, pos + self.pos) as usize;
+        let index = self.checked_index(pos)?;
         self.pixels.get(index)
     }
 
     #[inline(always)]
-    #[track_caller]
     pub fn get_mut(&mut self, pos: Position) -> Option<&mut T> {
-        // FIXME check over/underflow
-        let index = Self::pos_of(self.size.width, pos + self.pos) as usize;
+        let index = self.checked_index(pos)?;
+        self.touch(index..index + 1);
         self.pixels.get_mut(index)
     }
 
@@ -124,6 +211,7 @@ impl<T> Surface<T> {
         let len = self.pixels.len();
         let end = (start + stride).min(len);
 
+        self.touch(start..end);
         self.pixels[start..end].copy_from_slice(&row[..stride.min(end - start)]);
     }
 
@@ -143,6 +231,7 @@ impl<T> Surface<T> {
         let row = (y + 1) * width;
         let row = row..row + width;
 
+        self.touch(prev.clone());
         if let Ok([prev, next]) = self.pixels.get_disjoint_mut([prev, row]) {
             prev.copy_from_slice(next);
         }
@@ -164,6 +253,7 @@ impl<T> Surface<T> {
         let row = (y + 1) * width;
         let row = row..row + width;
 
+        self.touch(prev.clone());
         if let Ok([prev, next]) = self.pixels.get_disjoint_mut([prev, row]) {
             prev.clone_from_slice(next);
         }
@@ -189,6 +279,7 @@ impl<T> Surface<T> {
         let len = self.pixels.len();
         let end = (start + stride).min(len);
 
+        self.touch(start..end);
         self.pixels[start..end].clone_from_slice(&row[..stride.min(end - start)]);
     }
 
@@ -203,6 +294,7 @@ impl<T> Surface<T> {
     pub fn iter_mut(
         &mut self,
     ) -> impl ExactSizeIterator<Item = (Position, &mut T)> + DoubleEndedIterator {
+        self.damage_all();
         self.pixels.iter_mut().enumerate().map(|(i, p)| {
             let x = i as u32 % self.size.width;
             let y = i as u32 / self.size.width;
@@ -210,6 +302,191 @@ impl<T> Surface<T> {
         })
     }
 
+    pub fn row(&self, y: u32) -> Option<&[T]> {
+        if y >= self.size.height {
+            return None;
+        }
+        let width = self.size.width as usize;
+        let start = y as usize * width;
+        self.pixels.get(start..start + width)
+    }
+
+    /// Moves the rows up by `lines`, or down if it's negative
+    ///
+    /// The rows that scroll out at one end come back in at the other
+    pub fn rotate_rows(&mut self, rows: std::ops::Range<u32>, lines: i32) {
+        let rows = rows.start.min(self.size.height)..rows.end.min(self.size.height);
+        if rows.is_empty() || self.size.width == 0 {
+            return;
+        }
+
+        let width = self.size.width as usize;
+        let cells = rows.start as usize * width..rows.end as usize * width;
+        self.touch(cells.clone());
+        let region = &mut self.pixels[cells];
+
+        let by = lines.unsigned_abs() as usize * width % region.len();
+        if lines > 0 {
+            region.rotate_left(by);
+        } else {
+            region.rotate_right(by);
+        }
+    }
+
+    /// Copies `src` onto this surface with its top left corner at `pos`, whatever doesn't fit is cut off
+    ///
+    /// Like [`Surface::get`], both surfaces' offsets apply, so `src`'s cell at `p` lands on `pos + p`
+    pub fn blit(&mut self, pos: Position, src: &Self)
+    where
+        T: Clone,
+    {
+        self.blit_with(pos, src, T::clone_from);
+    }
+
+    /// Like [`Surface::blit`], but `merge` decides what becomes of each covered cell, e.g. to blend it
+    pub fn blit_with<U>(
+        &mut self,
+        pos: Position,
+        src: &Surface<U>,
+        mut merge: impl FnMut(&mut T, &U),
+    ) {
+        let Some((dst, from, size)) = self.clip(pos - src.pos, src.size) else {
+            return;
+        };
+
+        let width = size.width as usize;
+        for y in 0..size.height {
+            let (to, from) = (self.index(dst.x, dst.y + y), src.index(from.x, from.y + y));
+            self.touch(to..to + width);
+            let row = &mut self.pixels[to..to + width];
+            for (cell, src) in row.iter_mut().zip(&src.pixels[from..from + width]) {
+                merge(cell, src)
+            }
+        }
+    }
+
+    /// Copies an area out into a new surface of `size`, the parts of it outside of this one are the default
+    pub fn sub_surface(&self, pos: Position, size: Size) -> Self
+    where
+        T: Clone,
+    {
+        let mut out = Self::new(size, self.default.clone());
+        let pos = Position::new(pos.x.saturating_neg(), pos.y.saturating_neg());
+        out.blit(pos, self);
+        out
+    }
+
+    /// Scrolls what's in an area by `by`, what moves out of it is dropped and the cells left behind are the default
+    ///
+    /// Like [`Surface::rotate_rows`] and [`Renderer::scroll`](crate::Renderer::scroll), a positive `by.y` moves
+    /// the content up and a positive `by.x` moves it to the left
+    pub fn scroll(&mut self, pos: Position, size: Size, by: Delta<i32>)
+    where
+        T: Clone,
+    {
+        let Some((area, _, size)) = self.clip(pos, size) else {
+            return;
+        };
+        if by == Delta::<i32>::ZERO {
+            return;
+        }
+
+        let (w, h) = (i64::from(size.width), i64::from(size.height));
+        let (dx, dy) = (-i64::from(by.x), -i64::from(by.y));
+        // walk against the direction of the move, so nothing is overwritten before it's copied
+        for j in 0..h {
+            let y = if dy > 0 { h - 1 - j } else { j };
+            let row = self.index(area.x, area.y + y as u32);
+            self.touch(row..row + size.width as usize);
+            for i in 0..w {
+                let x = if dx > 0 { w - 1 - i } else { i };
+                let to = self.index(area.x + x as u32, area.y + y as u32);
+                let (sx, sy) = (x - dx, y - dy);
+                self.pixels[to] = if (0..w).contains(&sx) && (0..h).contains(&sy) {
+                    self.pixels[self.index(area.x + sx as u32, area.y + sy as u32)].clone()
+                } else {
+                    self.default.clone()
+                };
+            }
+        }
+    }
+
+    /// Mirrors the surface left to right
+    pub fn flip_horizontal(&mut self) {
+        if self.size.width == 0 {
+            return;
+        }
+        for row in self.pixels.chunks_exact_mut(self.size.width as usize) {
+            row.reverse();
+        }
+        self.damage_all();
+    }
+
+    /// Mirrors the surface top to bottom
+    pub fn flip_vertical(&mut self) {
+        let width = self.size.width as usize;
+        let height = self.size.height as usize;
+        for y in 0..height / 2 {
+            let (top, bottom) = self.pixels.split_at_mut((height - 1 - y) * width);
+            top[y * width..(y + 1) * width].swap_with_slice(&mut bottom[..width]);
+        }
+        self.damage_all();
+    }
+
+    pub fn rotate_180(&mut self) {
+        self.pixels.reverse();
+        self.damage_all();
+    }
+
+    /// Turns the surface a quarter to the right, which swaps its width and height
+    pub fn rotate_clockwise(&mut self)
+    where
+        T: Clone,
+    {
+        let Size { width, height } = self.size;
+        self.pixels = (0..width)
+            .flat_map(|x| (0..height).rev().map(move |y| (x, y)))
+            .map(|(x, y)| self.pixels[self.index(x, y)].clone())
+            .collect();
+        self.size = Size::new(height, width);
+        self.damage_all();
+    }
+
+    /// Turns the surface a quarter to the left, which swaps its width and height
+    pub fn rotate_counter_clockwise(&mut self)
+    where
+        T: Clone,
+    {
+        let Size { width, height } = self.size;
+        self.pixels = (0..width)
+            .rev()
+            .flat_map(|x| (0..height).map(move |y| (x, y)))
+            .map(|(x, y)| self.pixels[self.index(x, y)].clone())
+            .collect();
+        self.size = Size::new(height, width);
+        self.damage_all();
+    }
+
+    /// Where an area of `size` placed at `pos` overlaps this surface, `pos` is offset like it is for [`Surface::get`]
+    ///
+    /// That's the overlap's top left corner here (without the offset), its top left corner inside of the area and its size
+    fn clip(&self, pos: Position, size: Size) -> Option<(Position<u32>, Position<u32>, Size)> {
+        let x = i64::from(pos.x) + i64::from(self.pos.x);
+        let y = i64::from(pos.y) + i64::from(self.pos.y);
+        let (x0, y0) = (x.max(0), y.max(0));
+        let x1 = (x + i64::from(size.width)).min(i64::from(self.size.width));
+        let y1 = (y + i64::from(size.height)).min(i64::from(self.size.height));
+        if x0 >= x1 || y0 >= y1 {
+            return None;
+        }
+
+        Some((
+            Position::new(x0 as u32, y0 as u32),
+            Position::new((x0 - x) as u32, (y0 - y) as u32),
+            Size::new((x1 - x0) as u32, (y1 - y0) as u32),
+        ))
+    }
+
     pub fn rows(&self) -> impl ExactSizeIterator<Item = (u32, &[T])> + DoubleEndedIterator {
         self.pixels
             .chunks_exact(self.size.width as usize)
@@ -220,6 +497,7 @@ impl<T> Surface<T> {
     pub fn rows_mut(
         &mut self,
     ) -> impl ExactSizeIterator<Item = (u32, &mut [T])> + DoubleEndedIterator {
+        self.damage_all();
         self.pixels
             .chunks_exact_mut(self.size.width as usize)
             .enumerate()
@@ -232,10 +510,7 @@ impl<T> std::ops::Index<Position<i32>> for Surface<T> {
     #[track_caller]
     #[inline]
     fn index(&self, index: Position<i32>) -> &Self::Output {
-        assert!(index.x >= 0, "pos.x cannot be negative");
-        assert!(index.y >= 0, "pos.y cannot be negative");
-        let index = Self::pos_of(self.size.width, index + self.pos) as usize;
-        &self.pixels[index]
+        &self.pixels[self.expect_index(index)]
     }
 }
 
@@ -243,9 +518,8 @@ impl<T> std::ops::IndexMut<Position<i32>> for Surface<T> {
     #[track_caller]
     #[inline]
     fn index_mut(&mut self, index: Position<i32>) -> &mut Self::Output {
-        assert!(index.x >= 0, "pos.x cannot be negative");
-        assert!(index.y >= 0, "pos.y cannot be negative");
-        let index = Self::pos_of(self.size.width, index + self.pos) as usize;
+        let index = self.expect_index(index);
+        self.touch(index..index + 1);
         &mut self.pixels[index]
     }
 }
@@ -255,9 +529,7 @@ impl<T> std::ops::Index<Position<u32>> for Surface<T> {
     #[track_caller]
     #[inline]
     fn index(&self, index: Position<u32>) -> &Self::Output {
-        let index = Position::new(index.x as _, index.y as _);
-        let index = Self::pos_of(self.size.width, index + self.pos) as usize;
-        &self.pixels[index]
+        &self[(index.x, index.y)]
     }
 }
 
@@ -265,9 +537,7 @@ impl<T> std::ops::IndexMut<Position<u32>> for Surface<T> {
     #[track_caller]
     #[inline]
     fn index_mut(&mut self, index: Position<u32>) -> &mut Self::Output {
-        let index = Position::new(index.x as _, index.y as _);
-        let index = Self::pos_of(self.size.width, index + self.pos) as usize;
-        &mut self.pixels[index]
+        &mut self[(index.x, index.y)]
     }
 }
 
@@ -276,9 +546,7 @@ impl<T> std::ops::Index<(u32, u32)> for Surface<T> {
     #[track_caller]
     #[inline]
     fn index(&self, (x, y): (u32, u32)) -> &Self::Output {
-        let index = Position::new(x as _, y as _);
-        let index = Self::pos_of(self.size.width, index + self.pos) as usize;
-        &self.pixels[index]
+        &self[unsigned(x, y)]
     }
 }
 
@@ -286,15 +554,234 @@ impl<T> std::ops::IndexMut<(u32, u32)> for Surface<T> {
     #[track_caller]
     #[inline]
     fn index_mut(&mut self, (x, y): (u32, u32)) -> &mut Self::Output {
-        let index = Position::new(x as _, y as _);
-        let index = Self::pos_of(self.size.width, index + self.pos) as usize;
-        &mut self.pixels[index]
+        &mut self[unsigned(x, y)]
     }
 }
 
+/// Anything too large to be a signed position is out of bounds anyway
+#[inline(always)]
+fn unsigned(x: u32, y: u32) -> Position {
+    let clamp = |n: u32| i32::try_from(n).unwrap_or(i32::MAX);
+    Position::new(clamp(x), clamp(y))
+}
+
 #[derive(Copy, Clone, Default, Debug, PartialEq)]
 pub enum ResizeMode {
     Keep,
     #[default]
     Discard,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A surface where each cell is `y * 10 + x`
+    fn numbered(width: u32, height: u32) -> Surface<u32> {
+        let mut surface = Surface::new(Size::new(width, height), 0);
+        for (pos, cell) in surface.iter_mut() {
+            *cell = pos.y as u32 * 10 + pos.x as u32;
+        }
+        surface
+    }
+
+    fn cells<T: Clone>(surface: &Surface<T>) -> Vec<Vec<T>> {
+        surface.rows().map(|(_, row)| row.to_vec()).collect()
+    }
+
+    #[test]
+    fn blit_clips() {
+        let mut dst = Surface::new(Size::new(3, 3), 0);
+        dst.blit(Position::new(-1, 1), &numbered(3, 3));
+        assert_eq!(cells(&dst), [[0, 0, 0], [1, 2, 0], [11, 12, 0]]);
+    }
+
+    #[test]
+    fn blit_with_offsets() {
+        let mut dst = Surface::new(Size::new(3, 3), 0).with_offset(Position::new(1, 0));
+        dst.blit(Position::ZERO, &numbered(2, 2));
+        assert_eq!(cells(&dst), [[0, 0, 1], [0, 10, 11], [0, 0, 0]]);
+
+        // the source's offset picks which of its cells is at its origin
+        let mut dst = Surface::new(Size::new(3, 3), 0);
+        let src = numbered(2, 2).with_offset(Position::new(1, 0));
+        dst.blit(Position::new(1, 1), &src);
+        assert_eq!(cells(&dst), [[0, 0, 0], [0, 1, 0], [10, 11, 0]]);
+    }
+
+    #[test]
+    fn sub_surface() {
+        let src = numbered(4, 4);
+        let sub = src.sub_surface(Position::new(2, 1), Size::new(3, 2));
+        assert_eq!(cells(&sub), [[12, 13, 0], [22, 23, 0]]);
+
+        let src = src.with_offset(Position::new(1, 1));
+        let sub = src.sub_surface(Position::new(0, 0), Size::new(2, 2));
+        assert_eq!(cells(&sub), [[11, 12], [21, 22]]);
+        assert_eq!(sub[(0, 0)], src[Position::new(0, 0)]);
+    }
+
+    #[test]
+    fn scroll_matches_rotate_rows() {
+        let mut scrolled = numbered(2, 4);
+        scrolled.scroll(Position::ZERO, Size::new(2, 4), Delta { x: 0, y: 1 });
+        assert_eq!(cells(&scrolled), [[10, 11], [20, 21], [30, 31], [0, 0]]);
+
+        let mut rotated = numbered(2, 4);
+        rotated.rotate_rows(0..4, 1);
+        assert_eq!(cells(&rotated)[..3], cells(&scrolled)[..3]);
+
+        let mut scrolled = numbered(2, 4);
+        scrolled.scroll(Position::ZERO, Size::new(2, 4), Delta { x: 0, y: -1 });
+        assert_eq!(cells(&scrolled), [[0, 0], [0, 1], [10, 11], [20, 21]]);
+
+        let mut rotated = numbered(2, 4);
+        rotated.rotate_rows(0..4, -1);
+        assert_eq!(cells(&rotated)[1..], cells(&scrolled)[1..]);
+    }
+
+    #[test]
+    fn scroll_area() {
+        let mut surface = numbered(3, 3);
+        surface.scroll(Position::new(1, 1), Size::new(2, 2), Delta { x: 1, y: 0 });
+        assert_eq!(cells(&surface), [[0, 1, 2], [10, 12, 0], [20, 22, 0]]);
+
+        // the area is offset too
+        let mut surface = numbered(3, 3).with_offset(Position::new(1, 1));
+        surface.scroll(Position::ZERO, Size::new(2, 2), Delta { x: 0, y: 1 });
+        assert_eq!(cells(&surface), [[0, 1, 2], [10, 21, 22], [20, 0, 0]]);
+    }
+
+    #[test]
+    fn rotations() {
+        let original = cells(&numbered(3, 2));
+
+        let mut surface = numbered(3, 2);
+        surface.rotate_clockwise();
+        assert_eq!(surface.size(), Size::new(2, 3));
+        assert_eq!(cells(&surface), [[10, 0], [11, 1], [12, 2]]);
+
+        surface.rotate_counter_clockwise();
+        assert_eq!(cells(&surface), original);
+
+        for _ in 0..4 {
+            surface.rotate_clockwise();
+        }
+        assert_eq!(cells(&surface), original);
+
+        surface.rotate_180();
+        assert_eq!(cells(&surface), [[12, 11, 10], [2, 1, 0]]);
+        surface.rotate_180();
+        assert_eq!(cells(&surface), original);
+    }
+
+    #[test]
+    fn flips() {
+        let mut surface = numbered(3, 3);
+        surface.flip_horizontal();
+        assert_eq!(cells(&surface), [[2, 1, 0], [12, 11, 10], [22, 21, 20]]);
+
+        surface.flip_vertical();
+        assert_eq!(cells(&surface), [[22, 21, 20], [12, 11, 10], [2, 1, 0]]);
+
+        // both flips make a half turn
+        let mut rotated = numbered(3, 3);
+        rotated.rotate_180();
+        assert_eq!(cells(&surface), cells(&rotated));
+    }
+
+    #[test]
+    fn index_with_offset() {
+        let surface = numbered(3, 3).with_offset(Position::new(1, 1));
+        assert_eq!(surface[(0, 0)], 11);
+        assert_eq!(surface[Position::new(-1, -1)], 0_u32);
+        assert_eq!(surface.get(Position::new(1, 1)), Some(&surface[(1, 1)]));
+    }
+
+    fn damage<T>(surface: &Surface<T>) -> Vec<(u32, Range<u32>)> {
+        surface.damage().collect()
+    }
+
+    #[test]
+    fn damage_is_tracked() {
+        let mut surface = Surface::new(Size::new(5, 4), 0);
+        assert_eq!(
+            damage(&surface),
+            [(0, 0..5), (1, 0..5), (2, 0..5), (3, 0..5)]
+        );
+
+        surface.clear_damage();
+        assert!(!surface.is_damaged());
+
+        // reading doesn't count
+        _ = surface.get(Position::new(1, 1));
+        _ = surface[(1, 1)];
+        assert!(!surface.is_damaged());
+
+        surface.set(Position::new(1, 1), 1);
+        surface[(3, 1)] = 1;
+        surface[Position::new(2, 3)] = 1;
+        assert_eq!(damage(&surface), [(1, 1..4), (3, 2..3)]);
+        assert_eq!(surface.row_damage(1), Some(1..4));
+        assert_eq!(surface.row_damage(2), None);
+
+        // writing the same value still counts
+        surface.clear_damage();
+        *surface.get_mut(Position::new(0, 0)).unwrap() = 0;
+        assert_eq!(damage(&surface), [(0, 0..1)]);
+
+        // and nothing outside of the surface does
+        surface.clear_damage();
+        surface.set(Position::new(5, 0), 1);
+        surface.set(Position::new(-1, 0), 1);
+        assert!(!surface.is_damaged());
+    }
+
+    #[test]
+    fn damage_spans_rows() {
+        let mut surface = Surface::new(Size::new(5, 4), 0);
+        surface.clear_damage();
+        surface.copy_row(Position::new(2, 1), &[1, 1]);
+        assert_eq!(damage(&surface), [(1, 2..4)]);
+
+        surface.clear_damage();
+        surface.blit(Position::new(3, 2), &numbered(4, 4));
+        assert_eq!(damage(&surface), [(2, 3..5), (3, 3..5)]);
+
+        surface.clear_damage();
+        surface.rotate_rows(1..3, 1);
+        assert_eq!(damage(&surface), [(1, 0..5), (2, 0..5)]);
+
+        surface.clear_damage();
+        surface.scroll(Position::new(1, 0), Size::new(2, 2), Delta { x: 0, y: 1 });
+        assert_eq!(damage(&surface), [(0, 1..3), (1, 1..3)]);
+
+        // everything is damaged by whole surface changes
+        for change in [
+            |s: &mut Surface<u32>| s.fill(1),
+            |s: &mut Surface<u32>| s.flip_horizontal(),
+            |s: &mut Surface<u32>| s.rotate_clockwise(),
+            |s: &mut Surface<u32>| s.resize(Size::new(2, 2), ResizeMode::Keep),
+        ] {
+            surface.clear_damage();
+            change(&mut surface);
+            let Size { width, height } = surface.size();
+            let all = (0..height).map(|y| (y, 0..width)).collect::<Vec<_>>();
+            assert_eq!(damage(&surface), all);
+        }
+    }
+
+    #[test]
+    #[should_panic = "outside of the surface"]
+    fn index_past_the_width() {
+        let surface = numbered(3, 3);
+        _ = surface[(3, 0)];
+    }
+
+    #[test]
+    #[should_panic = "outside of the surface"]
+    fn index_mut_past_the_offset() {
+        let mut surface = numbered(3, 3).with_offset(Position::new(1, 1));
+        surface[(2, 0)] = 0;
+    }
+}
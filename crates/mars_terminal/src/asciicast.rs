@@ -0,0 +1,266 @@
+use std::{
+    fmt::Write as _,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use mars_math::{Position, Size};
+
+use crate::{Backend, Clipboard, Event, HoverRegions};
+
+/// Wraps a [`Backend`], writing everything sent to it as an asciicast v2 recording to `out`
+///
+/// Each flush of the output becomes one `"o"` event, rasterizers flush once a frame has ended so it is played back in one piece.
+/// Resizes read from the backend become `"r"` events
+pub struct AsciicastRecorder<B, W> {
+    inner: B,
+    out: W,
+    start: Instant,
+    pending: Vec<u8>,
+}
+
+impl<B: Backend, W: std::io::Write> AsciicastRecorder<B, W> {
+    /// Writes the header, with the size of the backend's screen
+    ///
+    /// The output of an inline viewport is positioned on the whole screen, so that's what is recorded
+    pub fn new(inner: B, mut out: W) -> std::io::Result<Self> {
+        let Size { width, height } = inner.screen_size();
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+
+        write!(
+            &mut out,
+            r#"{{"version": 2, "width": {width}, "height": {height}, "timestamp": {timestamp}"#
+        )?;
+        if let Ok(term) = std::env::var("TERM") {
+            write!(&mut out, r#", "env": {{"TERM": "{}"}}"#, escape(&term))?;
+        }
+        writeln!(&mut out, "}}")?;
+        out.flush()?;
+
+        Ok(Self {
+            inner,
+            out,
+            start: Instant::now(),
+            pending: Vec::new(),
+        })
+    }
+
+    pub fn into_inner(self) -> (B, W) {
+        (self.inner, self.out)
+    }
+
+    fn write_event(&mut self, code: char, data: &str) -> std::io::Result<()> {
+        let time = self.start.elapsed().as_secs_f64();
+        writeln!(
+            &mut self.out,
+            r#"[{time:.6}, "{code}", "{}"]"#,
+            escape(data)
+        )?;
+        self.out.flush()
+    }
+}
+
+impl<B: Backend, W: std::io::Write> std::io::Write for AsciicastRecorder<B, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.pending.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()?;
+
+        let pending = std::mem::take(&mut self.pending);
+        let mut data = String::with_capacity(pending.len());
+        let mut rest = pending.as_slice();
+        while !rest.is_empty() {
+            let err = match std::str::from_utf8(rest) {
+                Ok(valid) => {
+                    data.push_str(valid);
+                    break;
+                }
+                Err(err) => err,
+            };
+
+            let (valid, invalid) = rest.split_at(err.valid_up_to());
+            data.push_str(std::str::from_utf8(valid).expect("checked above"));
+            match err.error_len() {
+                // these bytes will never be valid, so they're replaced like `String::from_utf8_lossy` does
+                Some(len) => {
+                    data.push(char::REPLACEMENT_CHARACTER);
+                    rest = &invalid[len..];
+                }
+                // a multi-byte character split across flushes goes out with the next one
+                None => {
+                    self.pending.extend_from_slice(invalid);
+                    break;
+                }
+            }
+        }
+
+        if data.is_empty() {
+            return Ok(());
+        }
+        self.write_event('o', &data)
+    }
+}
+
+impl<B: Backend, W: std::io::Write> Backend for AsciicastRecorder<B, W> {
+    fn size(&self) -> Size {
+        self.inner.size()
+    }
+
+    fn screen_size(&self) -> Size {
+        self.inner.screen_size()
+    }
+
+    fn origin(&self) -> Position {
+        self.inner.origin()
+    }
+
+    fn try_read_event(&mut self) -> Option<Event> {
+        let ev = self.inner.try_read_event()?;
+        if let Event::Resize { .. } = &ev {
+            let size = self.inner.screen_size();
+            let size = format!("{}x{}", size.width, size.height);
+            // a failed write shouldn't take the application down with it, the recording just ends early
+            _ = self.write_event('r', &size);
+        }
+        Some(ev)
+    }
+
+    fn clipboard(&self) -> Clipboard {
+        self.inner.clipboard()
+    }
+
+    fn hover_regions(&self) -> HoverRegions {
+        self.inner.hover_regions()
+    }
+
+    fn flush_clipboard(&mut self) -> std::io::Result<()> {
+        self.inner.flush_clipboard()
+    }
+
+    fn suspend(&mut self) -> std::io::Result<()> {
+        self.inner.suspend()
+    }
+
+    fn resume(&mut self) -> std::io::Result<()> {
+        self.inner.resume()
+    }
+}
+
+/// Escapes the text for a JSON string
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            ch if ch.is_control() => _ = write!(&mut out, "\\u{:04x}", ch as u32),
+            ch => out.push(ch),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write as _;
+
+    use super::*;
+    use crate::MockTerminal;
+
+    fn recorder(
+        events: impl IntoIterator<Item = Event>,
+    ) -> AsciicastRecorder<MockTerminal, Vec<u8>> {
+        let inner = MockTerminal::new(Size::new(80, 24)).events(events);
+        AsciicastRecorder::new(inner, Vec::new()).unwrap()
+    }
+
+    // the events without their time
+    fn events(out: &[u8]) -> Vec<String> {
+        std::str::from_utf8(out)
+            .unwrap()
+            .lines()
+            .skip(1)
+            .map(|line| {
+                let (time, event) = line
+                    .strip_prefix('[')
+                    .and_then(|line| line.split_once(", "))
+                    .unwrap();
+                let (secs, fraction) = time.split_once('.').unwrap();
+                assert!(secs.parse::<u64>().is_ok() && fraction.len() == 6, "{line}");
+                format!("[{event}")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn format() {
+        let mut recorder = recorder([Event::Resize {
+            size: Size::new(100, 30),
+        }]);
+        write!(recorder, "\x1b[1;1Hhi \"there\"\\\r\n").unwrap();
+        recorder.flush().unwrap();
+
+        // nothing was written since the last flush
+        recorder.flush().unwrap();
+
+        assert_eq!(
+            recorder.try_read_event(),
+            Some(Event::Resize {
+                size: Size::new(100, 30)
+            })
+        );
+
+        let (inner, out) = recorder.into_inner();
+        assert_eq!(inner.output(), b"\x1b[1;1Hhi \"there\"\\\r\n");
+
+        let header = std::str::from_utf8(&out).unwrap().lines().next().unwrap();
+        assert!(
+            header.starts_with(r#"{"version": 2, "width": 80, "height": 24, "timestamp": "#),
+            "{header}"
+        );
+        assert!(header.ends_with('}'), "{header}");
+
+        assert_eq!(
+            events(&out),
+            [
+                r#"["o", "\u001b[1;1Hhi \"there\"\\\r\n"]"#,
+                r#"["r", "100x30"]"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn split_codepoint() {
+        let mut recorder = recorder([]);
+
+        // é is 0xc3 0xa9
+        recorder.write_all(b"a\xc3").unwrap();
+        recorder.flush().unwrap();
+        recorder.write_all(b"\xa9b").unwrap();
+        recorder.flush().unwrap();
+
+        // only the first byte of a character
+        recorder.write_all(b"\xc3").unwrap();
+        recorder.flush().unwrap();
+        recorder.flush().unwrap();
+
+        // invalid bytes are replaced rather than held on to
+        recorder.write_all(b"c\xffd").unwrap();
+        recorder.flush().unwrap();
+        assert!(recorder.pending.is_empty());
+
+        let (_, out) = recorder.into_inner();
+        assert_eq!(
+            events(&out),
+            [r#"["o", "a"]"#, r#"["o", "éb"]"#, r#"["o", "�c�d"]"#]
+        );
+    }
+}
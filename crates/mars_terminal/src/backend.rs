@@ -0,0 +1,116 @@
+use mars_math::{Position, Size};
+
+use crate::{Clipboard, Event, HoverRegions, Terminal};
+
+/// Something an application can run on, the real [`Terminal`] or a [`MockTerminal`](crate::MockTerminal)
+///
+/// Rendered output is written to the backend with [`std::io::Write`]
+pub trait Backend: std::io::Write {
+    /// The size of the area the application draws to
+    fn size(&self) -> Size;
+
+    /// The size of the whole screen, this is larger than [`Backend::size`] with an inline viewport
+    fn screen_size(&self) -> Size {
+        self.size()
+    }
+
+    /// Where the area the application draws to starts on the screen
+    fn origin(&self) -> Position {
+        Position::ZERO
+    }
+
+    /// The next event, if there is one, without blocking
+    fn try_read_event(&mut self) -> Option<Event>;
+
+    fn clipboard(&self) -> Clipboard;
+    fn hover_regions(&self) -> HoverRegions;
+
+    /// Sends any requests queued on the [`Clipboard`] handles
+    fn flush_clipboard(&mut self) -> std::io::Result<()>;
+
+    /// Handles an [`Event::Suspend`], returning once the application should continue
+    fn suspend(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    /// Handles an [`Event::Resume`], setting the backend up again
+    fn resume(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Backend for Terminal {
+    fn size(&self) -> Size {
+        Self::size(self)
+    }
+
+    fn screen_size(&self) -> Size {
+        Self::screen_size(self)
+    }
+
+    fn origin(&self) -> Position {
+        Self::origin(self)
+    }
+
+    fn try_read_event(&mut self) -> Option<Event> {
+        Self::try_read_event(self)
+    }
+
+    fn clipboard(&self) -> Clipboard {
+        Self::clipboard(self)
+    }
+
+    fn hover_regions(&self) -> HoverRegions {
+        Self::hover_regions(self)
+    }
+
+    fn flush_clipboard(&mut self) -> std::io::Result<()> {
+        Self::flush_clipboard(self)
+    }
+
+    fn suspend(&mut self) -> std::io::Result<()> {
+        Self::suspend(self)
+    }
+
+    fn resume(&mut self) -> std::io::Result<()> {
+        Self::resume(self)
+    }
+}
+
+impl<T: Backend + ?Sized> Backend for &mut T {
+    fn size(&self) -> Size {
+        (**self).size()
+    }
+
+    fn screen_size(&self) -> Size {
+        (**self).screen_size()
+    }
+
+    fn origin(&self) -> Position {
+        (**self).origin()
+    }
+
+    fn try_read_event(&mut self) -> Option<Event> {
+        (**self).try_read_event()
+    }
+
+    fn clipboard(&self) -> Clipboard {
+        (**self).clipboard()
+    }
+
+    fn hover_regions(&self) -> HoverRegions {
+        (**self).hover_regions()
+    }
+
+    fn flush_clipboard(&mut self) -> std::io::Result<()> {
+        (**self).flush_clipboard()
+    }
+
+    fn suspend(&mut self) -> std::io::Result<()> {
+        (**self).suspend()
+    }
+
+    fn resume(&mut self) -> std::io::Result<()> {
+        (**self).resume()
+    }
+}
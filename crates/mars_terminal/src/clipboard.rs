@@ -0,0 +1,260 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ClipboardSelection {
+    /// The system clipboard
+    #[default]
+    Clipboard,
+    /// The primary selection, usually pasted with the middle mouse button
+    Primary,
+}
+
+impl ClipboardSelection {
+    const fn as_char(&self) -> char {
+        match self {
+            Self::Clipboard => 'c',
+            Self::Primary => 'p',
+        }
+    }
+
+    fn from_char(ch: char) -> Option<Self> {
+        match ch {
+            'c' => Some(Self::Clipboard),
+            'p' | 's' => Some(Self::Primary),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum ClipboardRequest {
+    Copy(ClipboardSelection, String),
+    Read(ClipboardSelection),
+}
+
+/// A handle for using the clipboard without holding the [`Terminal`](crate::Terminal)
+///
+/// This is a shared handle, clones refer to the same queue of requests.
+/// Requests are sent by [`Terminal::flush_clipboard`](crate::Terminal::flush_clipboard)
+#[derive(Clone, Debug, Default)]
+pub struct Clipboard {
+    requests: Arc<Mutex<VecDeque<ClipboardRequest>>>,
+    // reads sent to the terminal that haven't been answered yet
+    pending_replies: Arc<AtomicUsize>,
+}
+
+impl Clipboard {
+    /// The most bytes of text that'll be sent to the terminal
+    ///
+    /// Terminals commonly limit OSC 52 to around 100kB of base64, this is the text that fits in that
+    pub const MAX_LEN: usize = 75_000;
+
+    /// Copies the text to the system clipboard
+    pub fn copy(&self, text: impl Into<String>) -> std::io::Result<()> {
+        self.copy_to(ClipboardSelection::Clipboard, text)
+    }
+
+    pub fn copy_to(
+        &self,
+        selection: ClipboardSelection,
+        text: impl Into<String>,
+    ) -> std::io::Result<()> {
+        let text = text.into();
+        check_len(&text)?;
+        self.lock()
+            .push_back(ClipboardRequest::Copy(selection, text));
+        Ok(())
+    }
+
+    /// Asks the terminal for the contents of the selection
+    ///
+    /// If the terminal allows it, the reply arrives as an [`Event::Clipboard`](crate::Event::Clipboard)
+    pub fn request(&self, selection: ClipboardSelection) {
+        self.lock().push_back(ClipboardRequest::Read(selection));
+    }
+
+    pub(crate) fn drain(&self) -> impl Iterator<Item = ClipboardRequest> + use<> {
+        std::mem::take(&mut *self.lock()).into_iter()
+    }
+
+    pub(crate) fn expect_reply(&self) {
+        self.pending_replies.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub(crate) fn is_expecting_reply(&self) -> bool {
+        self.pending_replies.load(Ordering::SeqCst) > 0
+    }
+
+    pub(crate) fn reply_received(&self) {
+        _ = self
+            .pending_replies
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1));
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<ClipboardRequest>> {
+        self.requests.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+pub(crate) fn check_len(text: &str) -> std::io::Result<()> {
+    if text.len() > Clipboard::MAX_LEN {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!(
+                "clipboard text is {} bytes, the limit is {} bytes",
+                text.len(),
+                Clipboard::MAX_LEN
+            ),
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) fn write_copy(
+    out: &mut impl std::io::Write,
+    selection: ClipboardSelection,
+    text: &str,
+) -> std::io::Result<()> {
+    check_len(text)?;
+    write!(
+        out,
+        "\x1b]52;{};{}\x1b\\",
+        selection.as_char(),
+        encode(text.as_bytes())
+    )
+}
+
+pub(crate) fn write_read(
+    out: &mut impl std::io::Write,
+    selection: ClipboardSelection,
+) -> std::io::Result<()> {
+    write!(out, "\x1b]52;{};?\x1b\\", selection.as_char())
+}
+
+/// Could `body` be the start of an OSC 52 reply?
+pub(crate) fn is_reply_prefix(body: &str) -> bool {
+    body.starts_with("52;") || "52;".starts_with(body)
+}
+
+/// Parses the body of an OSC 52 reply, e.g. `52;c;aGVsbG8=` from `ESC ] 52 ; c ; aGVsbG8= ESC \`
+pub(crate) fn parse_reply(body: &str) -> Option<(ClipboardSelection, String)> {
+    let (selection, data) = body.strip_prefix("52;")?.split_once(';')?;
+    if data == "?" {
+        return None;
+    }
+
+    // the reply can list several selections, they all have the same contents
+    let selection = selection
+        .chars()
+        .find_map(ClipboardSelection::from_char)
+        .unwrap_or_default();
+    let data = decode(data)?;
+    Some((selection, String::from_utf8_lossy(&data).into_owned()))
+}
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn encode(input: &[u8]) -> String {
+    let mut out = String::with_capacity(input.len().div_ceil(3) * 4);
+    for chunk in input.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - i * 8));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - i * 6)) as usize & 0x3F] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Decodes padded base64, anything else is rejected
+fn decode(input: &str) -> Option<Vec<u8>> {
+    let data = input.trim_end_matches('=');
+    if !input.len().is_multiple_of(4) || input.len() - data.len() > 2 {
+        return None;
+    }
+
+    let input = data.as_bytes();
+    let mut out = Vec::with_capacity(input.len() * 3 / 4);
+    let mut n = 0u32;
+    let mut bits = 0;
+    for &c in input {
+        let value = ALPHABET.iter().position(|&a| a == c)? as u32;
+        n = (n << 6) | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((n >> bits) as u8);
+            n &= (1 << bits) - 1;
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reply() {
+        assert_eq!(
+            parse_reply("52;c;aGVsbG8="),
+            Some((ClipboardSelection::Clipboard, "hello".to_string()))
+        );
+        assert_eq!(
+            parse_reply("52;s0;aGVsbG8="),
+            Some((ClipboardSelection::Primary, "hello".to_string()))
+        );
+        assert_eq!(parse_reply("52;c;?"), None);
+        assert_eq!(parse_reply("11;rgb:0000/0000/0000"), None);
+    }
+
+    #[test]
+    fn reply_prefix() {
+        for body in ["", "5", "52", "52;", "52;c;aGVs"] {
+            assert!(is_reply_prefix(body), "{body}");
+        }
+        for body in ["1", "51", "5x", "x52;"] {
+            assert!(!is_reply_prefix(body), "{body}");
+        }
+    }
+
+    #[test]
+    fn base64_round_trip() {
+        for text in ["", "a", "ab", "abc", "hello, world", "ünïcödé"] {
+            let encoded = encode(text.as_bytes());
+            assert_eq!(decode(&encoded).as_deref(), Some(text.as_bytes()), "{text}");
+        }
+        assert_eq!(encode(b"hello"), "aGVsbG8=");
+    }
+
+    #[test]
+    fn base64_invalid() {
+        // unpadded, too much padding, padding in the middle and characters outside of the alphabet
+        for input in [
+            "aGVsbG8",
+            "aGk",
+            "a",
+            "aGVs====",
+            "aG==bG8=",
+            "aGVsbG8==",
+            "aGV$bG8=",
+        ] {
+            assert_eq!(decode(input), None, "{input}");
+        }
+        assert_eq!(decode("aGk=").as_deref(), Some(&b"hi"[..]));
+        assert_eq!(decode("").as_deref(), Some(&b""[..]));
+    }
+}
@@ -0,0 +1,63 @@
+use std::sync::{Arc, Mutex};
+
+use mars_math::{Position, Size};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RegionId(pub u32);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Region {
+    id: RegionId,
+    pos: Position,
+    size: Size,
+}
+
+impl Region {
+    fn contains(&self, pos: Position) -> bool {
+        let Some(offset) = (pos - self.pos).to_unsigned_checked() else {
+            return false;
+        };
+        offset.x < self.size.width && offset.y < self.size.height
+    }
+}
+
+/// Regions of the screen which produce [`Event::MouseEnter`](crate::Event::MouseEnter) and
+/// [`Event::MouseLeave`](crate::Event::MouseLeave) when the mouse crosses them
+///
+/// This is a shared handle, clones refer to the same set of regions.
+/// Changes are picked up with the next mouse event.
+#[derive(Clone, Debug, Default)]
+pub struct HoverRegions {
+    regions: Arc<Mutex<Vec<Region>>>,
+}
+
+impl HoverRegions {
+    /// Adds a region, replacing any previous region with the same id
+    pub fn insert(&self, id: RegionId, pos: Position, size: Size) {
+        let mut regions = self.lock();
+        let region = Region { id, pos, size };
+        match regions.iter_mut().find(|r| r.id == id) {
+            Some(old) => *old = region,
+            None => regions.push(region),
+        }
+    }
+
+    pub fn remove(&self, id: RegionId) {
+        self.lock().retain(|r| r.id != id);
+    }
+
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    pub(crate) fn hit_test(&self, pos: Position, mut hit: impl FnMut(RegionId)) {
+        for region in self.lock().iter().filter(|r| r.contains(pos)) {
+            hit(region.id)
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Region>> {
+        // a panic while holding the lock can't leave the list in an invalid state
+        self.regions.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
@@ -0,0 +1,339 @@
+use crate::{Key, KeyModifiers, Keybind, ModifierKey, ascii_is_uppercase_symbols};
+
+const MODIFIERS: [(KeyModifiers, &str); 6] = [
+    (KeyModifiers::CONTROL, "ctrl"),
+    (KeyModifiers::ALT, "alt"),
+    (KeyModifiers::SHIFT, "shift"),
+    (KeyModifiers::SUPER, "super"),
+    (KeyModifiers::HYPER, "hyper"),
+    (KeyModifiers::META, "meta"),
+];
+
+const NAMED_KEYS: [(Key, &str); 22] = [
+    (Key::Enter, "enter"),
+    (Key::Backspace, "backspace"),
+    (Key::Tab, "tab"),
+    (Key::Escape, "esc"),
+    (Key::Left, "left"),
+    (Key::Right, "right"),
+    (Key::Up, "up"),
+    (Key::Down, "down"),
+    (Key::Home, "home"),
+    (Key::End, "end"),
+    (Key::BackTab, "backtab"),
+    (Key::PageUp, "pageup"),
+    (Key::PageDown, "pagedown"),
+    (Key::Insert, "insert"),
+    (Key::Delete, "delete"),
+    (Key::KeypadBegin, "begin"),
+    (Key::CapsLock, "capslock"),
+    (Key::ScrollLock, "scrolllock"),
+    (Key::NumLock, "numlock"),
+    (Key::PrintScreen, "printscreen"),
+    (Key::Pause, "pause"),
+    (Key::Menu, "menu"),
+];
+
+const ALIASES: [(Key, &str); 11] = [
+    (Key::Char(' '), "space"),
+    (Key::Char('<'), "lt"),
+    (Key::Enter, "return"),
+    (Key::Enter, "cr"),
+    (Key::Backspace, "bs"),
+    (Key::Escape, "escape"),
+    (Key::PageUp, "pgup"),
+    (Key::PageDown, "pgdn"),
+    (Key::Insert, "ins"),
+    (Key::Delete, "del"),
+    (Key::Null, "nul"),
+];
+
+const MODIFIER_KEYS: [(ModifierKey, &str); 12] = [
+    (ModifierKey::LeftShift, "leftshift"),
+    (ModifierKey::LeftControl, "leftctrl"),
+    (ModifierKey::LeftAlt, "leftalt"),
+    (ModifierKey::LeftSuper, "leftsuper"),
+    (ModifierKey::LeftHyper, "lefthyper"),
+    (ModifierKey::LeftMeta, "leftmeta"),
+    (ModifierKey::RightShift, "rightshift"),
+    (ModifierKey::RightControl, "rightctrl"),
+    (ModifierKey::RightAlt, "rightalt"),
+    (ModifierKey::RightSuper, "rightsuper"),
+    (ModifierKey::RightHyper, "righthyper"),
+    (ModifierKey::RightMeta, "rightmeta"),
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseKeybindError {
+    Empty,
+    MissingKey { input: String },
+    UnknownModifier { modifier: String },
+    DuplicateModifier { modifier: String },
+    UnknownKey { key: String },
+}
+
+impl std::fmt::Display for ParseKeybindError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => f.write_str("keybind is empty"),
+            Self::MissingKey { input } => {
+                write!(f, "keybind '{input}' has modifiers but no key")
+            }
+            Self::UnknownModifier { modifier } => write!(
+                f,
+                "unknown modifier '{modifier}', expected one of: ctrl, alt, shift, super, hyper, meta"
+            ),
+            Self::DuplicateModifier { modifier } => {
+                write!(f, "modifier '{modifier}' was specified more than once")
+            }
+            Self::UnknownKey { key } => write!(
+                f,
+                "unknown key '{key}', expected a single character, a function key like 'f5' or a name like 'enter'"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParseKeybindError {}
+
+/// Parses keybinds like `ctrl+shift+a`, `C-x`, `alt+F5` and `<esc>`
+///
+/// Modifiers are separated from the key with either `+` or `-`. Single characters are case sensitive and,
+/// like [`Keybind::char`], an uppercase character implies `shift`. A lowercase character with `shift`
+/// is kept as written, terminals report shifted letters as uppercase so `A` is what matches shift+a.
+///
+/// The single letter modifiers are the emacs ones: `C-` is ctrl, `M-` and `A-` are alt, `S-` is shift,
+/// `s-` is super and `H-` is hyper. Terminals send emacs' meta as alt, so there's no letter for
+/// [`KeyModifiers::META`], write it as `meta+`
+impl std::str::FromStr for Keybind {
+    type Err = ParseKeybindError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let trimmed = input.trim();
+        let mut rest = match trimmed.strip_prefix('<').and_then(|s| s.strip_suffix('>')) {
+            Some(inner) if !inner.is_empty() => inner,
+            _ => trimmed,
+        };
+
+        if rest.is_empty() {
+            return Err(ParseKeybindError::Empty);
+        }
+
+        let mut modifiers = KeyModifiers::NONE;
+        // the first character is always part of the next token, this allows for things like `ctrl++` and `-`
+        while let Some((index, _)) = rest
+            .char_indices()
+            .skip(1)
+            .find(|&(_, c)| c == '+' || c == '-')
+        {
+            let (head, tail) = (&rest[..index], &rest[index + 1..]);
+            if tail.is_empty() {
+                return Err(ParseKeybindError::MissingKey {
+                    input: input.to_string(),
+                });
+            }
+
+            let modifier =
+                parse_modifier(head).ok_or_else(|| ParseKeybindError::UnknownModifier {
+                    modifier: head.to_string(),
+                })?;
+            if modifiers.contains(modifier) {
+                return Err(ParseKeybindError::DuplicateModifier {
+                    modifier: head.to_string(),
+                });
+            }
+            modifiers |= modifier;
+            rest = tail;
+        }
+
+        let mut keybind = parse_key(rest).ok_or_else(|| ParseKeybindError::UnknownKey {
+            key: rest.to_string(),
+        })?;
+
+        keybind.modifiers |= modifiers;
+        Ok(keybind)
+    }
+}
+
+fn parse_modifier(input: &str) -> Option<KeyModifiers> {
+    // the single letters follow emacs, so they're case sensitive
+    let modifier = match input {
+        "C" => KeyModifiers::CONTROL,
+        "M" | "A" => KeyModifiers::ALT,
+        "S" => KeyModifiers::SHIFT,
+        "s" => KeyModifiers::SUPER,
+        "H" => KeyModifiers::HYPER,
+        s if s.eq_ignore_ascii_case("control") => KeyModifiers::CONTROL,
+        s if s.eq_ignore_ascii_case("option") => KeyModifiers::ALT,
+        s if s.eq_ignore_ascii_case("opt") => KeyModifiers::ALT,
+        s if s.eq_ignore_ascii_case("cmd") => KeyModifiers::SUPER,
+        s if s.eq_ignore_ascii_case("win") => KeyModifiers::SUPER,
+        s => {
+            let (modifier, _) = MODIFIERS
+                .into_iter()
+                .find(|(_, name)| s.eq_ignore_ascii_case(name))?;
+            modifier
+        }
+    };
+    Some(modifier)
+}
+
+/// Parses modifiers joined with `+`, like `ctrl+alt`
+pub(crate) fn parse_modifiers(input: &str) -> Option<KeyModifiers> {
+    input
+        .split('+')
+        .try_fold(KeyModifiers::NONE, |modifiers, name| {
+            Some(modifiers | parse_modifier(name)?)
+        })
+}
+
+/// Formats modifiers joined with `+`, like `ctrl+alt`
+pub(crate) struct ModifierNames(pub(crate) KeyModifiers);
+
+impl std::fmt::Display for ModifierNames {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut names = MODIFIERS
+            .into_iter()
+            .filter(|(modifier, _)| self.0.contains(*modifier))
+            .map(|(_, name)| name);
+        if let Some(first) = names.next() {
+            f.write_str(first)?;
+        }
+        for name in names {
+            write!(f, "+{name}")?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_key(input: &str) -> Option<Keybind> {
+    let mut chars = input.chars();
+    if let (Some(ch), None) = (chars.next(), chars.next()) {
+        return Some(Keybind::char(ch));
+    }
+
+    let named = NAMED_KEYS
+        .into_iter()
+        .chain(ALIASES)
+        .find(|(_, name)| input.eq_ignore_ascii_case(name))
+        .map(|(key, _)| match key {
+            Key::Char(ch) => Keybind::char(ch),
+            key => Keybind::new(key),
+        });
+    if named.is_some() {
+        return named;
+    }
+
+    let modifier = MODIFIER_KEYS
+        .into_iter()
+        .find(|(_, name)| input.eq_ignore_ascii_case(name))
+        .map(|(key, _)| Key::Modifier(key));
+    if let Some(key) = modifier {
+        return Some(Keybind::new(key));
+    }
+
+    if input.eq_ignore_ascii_case("null") {
+        return Some(Keybind::new(Key::Null));
+    }
+
+    let function = input
+        .strip_prefix(['f', 'F'])
+        .filter(|s| s.bytes().all(|c| c.is_ascii_digit()))?;
+    function.parse().ok().map(Key::Function).map(Keybind::new)
+}
+
+/// Formats the keybind in its canonical form, e.g. `ctrl+alt+x`, `shift+f5` or `ctrl+A`
+///
+/// This can be parsed back with [`str::parse`]
+impl std::fmt::Display for Keybind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let implied_shift = match self.key {
+            Key::Char(ch) => ch.is_uppercase() || ascii_is_uppercase_symbols(ch),
+            _ => false,
+        };
+
+        for (modifier, name) in MODIFIERS {
+            if modifier == KeyModifiers::SHIFT && implied_shift {
+                continue;
+            }
+            if self.modifiers.contains(modifier) {
+                write!(f, "{name}+")?;
+            }
+        }
+
+        self.key.fmt(f)
+    }
+}
+
+impl std::fmt::Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Char(' ') => f.write_str("space"),
+            Self::Char(ch) => write!(f, "{ch}"),
+            Self::Function(n) => write!(f, "f{n}"),
+            Self::Modifier(key) => {
+                let (_, name) = MODIFIER_KEYS
+                    .into_iter()
+                    .find(|(k, _)| k == key)
+                    .expect("all modifier keys have a name");
+                f.write_str(name)
+            }
+            Self::Null => f.write_str("null"),
+            key => {
+                let (_, name) = NAMED_KEYS
+                    .into_iter()
+                    .find(|(k, _)| k == key)
+                    .expect("all keys have a name");
+                f.write_str(name)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let keybinds = [
+            Keybind::char('a'),
+            Keybind::char('a').shift(),
+            Keybind::char('A'),
+            Keybind::char('A').control(),
+            Keybind::char('!').alt(),
+            Keybind::char('+').control(),
+            Keybind::char('-'),
+            Keybind::char(' ').meta(),
+            Keybind::new(Key::Function(5)).shift().super_key(),
+            Keybind::new(Key::Enter).hyper(),
+            Keybind::new(Key::Modifier(ModifierKey::LeftShift)),
+            Keybind::new(Key::Null),
+        ];
+
+        for keybind in keybinds {
+            let s = keybind.to_string();
+            assert_eq!(s.parse::<Keybind>(), Ok(keybind), "{s}");
+        }
+    }
+
+    #[test]
+    fn emacs_modifiers() {
+        let cases = [
+            ("C-x", Keybind::char('x').control()),
+            ("M-x", Keybind::char('x').alt()),
+            ("A-x", Keybind::char('x').alt()),
+            ("S-f5", Keybind::new(Key::Function(5)).shift()),
+            ("s-x", Keybind::char('x').super_key()),
+            ("H-x", Keybind::char('x').hyper()),
+            ("meta+x", Keybind::char('x').meta()),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(input.parse::<Keybind>(), Ok(expected), "{input}");
+        }
+
+        assert!("m-x".parse::<Keybind>().is_err());
+    }
+}
@@ -0,0 +1,588 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+    time::{Duration, Instant},
+};
+
+use crate::{Event, Keybind, ParseKeybindError};
+
+/// Parses a whitespace separated sequence of keybinds, like `g g` or `ctrl+x ctrl+s`
+pub fn parse_key_sequence(input: &str) -> Result<Vec<Keybind>, ParseKeybindError> {
+    let sequence = input
+        .split_whitespace()
+        .map(str::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+    if sequence.is_empty() {
+        return Err(ParseKeybindError::Empty);
+    }
+    Ok(sequence)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KeymapEvent<A> {
+    /// A bound sequence was completed
+    Action(A),
+    /// The keys didn't form a bound sequence in the current mode
+    ///
+    /// These can be replayed, e.g. as text input in an insert mode
+    Unbound(Vec<Keybind>),
+}
+
+/// A key that continues the pending sequence, for showing a "which-key" popup
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Continuation<'a, A> {
+    pub keybind: Keybind,
+    /// The action this key completes, if any
+    pub action: Option<&'a A>,
+    /// Whether there are longer sequences starting with this key
+    pub is_prefix: bool,
+}
+
+#[derive(Debug)]
+struct Node<A> {
+    action: Option<A>,
+    children: HashMap<Keybind, Node<A>>,
+}
+
+impl<A> Default for Node<A> {
+    fn default() -> Self {
+        Self {
+            action: None,
+            children: HashMap::new(),
+        }
+    }
+}
+
+impl<A> Node<A> {
+    fn find(&self, sequence: &[Keybind]) -> Option<&Self> {
+        sequence
+            .iter()
+            .try_fold(self, |node, key| node.children.get(key))
+    }
+
+    /// Removes the action for the sequence, along with any nodes that are left without a purpose
+    fn remove(&mut self, sequence: &[Keybind]) -> Option<A> {
+        let Some((key, rest)) = sequence.split_first() else {
+            return self.action.take();
+        };
+        let child = self.children.get_mut(key)?;
+        let action = child.remove(rest);
+        if child.action.is_none() && child.children.is_empty() {
+            self.children.remove(key);
+        }
+        action
+    }
+}
+
+#[derive(Debug)]
+struct Layer<M, A> {
+    root: Node<A>,
+    parent: Option<M>,
+}
+
+impl<M, A> Default for Layer<M, A> {
+    fn default() -> Self {
+        Self {
+            root: Node::default(),
+            parent: None,
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Lookup<'a, A> {
+    action: Option<&'a A>,
+    is_prefix: bool,
+}
+
+/// Maps sequences of [`Keybind`]s to actions, grouped into modes
+///
+/// A mode can have a parent, sequences that aren't bound in a mode fall through to its parent.
+///
+/// When a sequence is both bound and the prefix of a longer sequence (e.g. `g` and `g g`) the keymap
+/// waits for the next key, or the timeout, before deciding which one was meant.
+#[derive(Debug)]
+pub struct Keymap<M, A> {
+    layers: HashMap<M, Layer<M, A>>,
+    mode: M,
+    pending: Vec<Keybind>,
+    last_key: Option<Instant>,
+    timeout: Option<Duration>,
+    queue: VecDeque<KeymapEvent<A>>,
+}
+
+impl<M, A> Keymap<M, A>
+where
+    M: Copy + Eq + Hash,
+    A: Clone,
+{
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+    pub fn new(mode: M) -> Self {
+        Self {
+            layers: HashMap::new(),
+            mode,
+            pending: Vec::new(),
+            last_key: None,
+            timeout: Some(Self::DEFAULT_TIMEOUT),
+            queue: VecDeque::new(),
+        }
+    }
+
+    /// How long to wait for the next key of a pending sequence, `None` waits forever
+    pub fn timeout(mut self, timeout: impl Into<Option<Duration>>) -> Self {
+        self.timeout = timeout.into();
+        self
+    }
+
+    /// Unbound sequences in `mode` will fall through to `parent`
+    pub fn with_parent(mut self, mode: M, parent: M) -> Self {
+        self.set_parent(mode, parent);
+        self
+    }
+
+    pub fn set_parent(&mut self, mode: M, parent: impl Into<Option<M>>) {
+        self.layers.entry(mode).or_default().parent = parent.into();
+    }
+
+    /// Binds a sequence of keys in `mode`, replacing any previous action for that sequence
+    pub fn bind(&mut self, mode: M, sequence: impl IntoIterator<Item = Keybind>, action: A) {
+        let layer = self.layers.entry(mode).or_default();
+        let node = sequence.into_iter().fold(&mut layer.root, |node, key| {
+            node.children.entry(key).or_default()
+        });
+        node.action = Some(action);
+    }
+
+    /// Binds a sequence like `ctrl+x ctrl+s`, see [`parse_key_sequence`]
+    pub fn bind_str(
+        &mut self,
+        mode: M,
+        sequence: &str,
+        action: A,
+    ) -> Result<(), ParseKeybindError> {
+        let sequence = parse_key_sequence(sequence)?;
+        self.bind(mode, sequence, action);
+        Ok(())
+    }
+
+    pub fn unbind(&mut self, mode: M, sequence: &[Keybind]) -> Option<A> {
+        self.layers.get_mut(&mode)?.root.remove(sequence)
+    }
+
+    pub const fn mode(&self) -> M {
+        self.mode
+    }
+
+    /// Switches to another mode, discarding any pending sequence
+    pub fn set_mode(&mut self, mode: M) {
+        self.mode = mode;
+        self.reset();
+    }
+
+    /// The keys of the sequence currently being entered
+    pub fn pending(&self) -> &[Keybind] {
+        &self.pending
+    }
+
+    pub fn is_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Discards the pending sequence
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.last_key = None;
+    }
+
+    /// The keys which continue the pending sequence in the current mode
+    ///
+    /// When nothing is pending this is every first key of the mode
+    pub fn continuations(&self) -> Vec<Continuation<'_, A>> {
+        let mut out: Vec<Continuation<'_, A>> = Vec::new();
+        for layer in self.chain() {
+            let Some(node) = layer.root.find(&self.pending) else {
+                continue;
+            };
+
+            for (&keybind, child) in &node.children {
+                let is_prefix = !child.children.is_empty();
+                match out.iter_mut().find(|c| c.keybind == keybind) {
+                    Some(existing) => {
+                        existing.action = existing.action.or(child.action.as_ref());
+                        existing.is_prefix |= is_prefix;
+                    }
+                    None => out.push(Continuation {
+                        keybind,
+                        action: child.action.as_ref(),
+                        is_prefix,
+                    }),
+                }
+            }
+        }
+        out
+    }
+
+    /// Feeds an event into the keymap
+    ///
+    /// Only key presses (and repeats) are considered, everything else is ignored
+    pub fn process(
+        &mut self,
+        event: &Event,
+        now: Instant,
+    ) -> impl Iterator<Item = KeymapEvent<A>> + '_ {
+        if let &Event::KeyPress { key, modifiers } | &Event::KeyRepeat { key, modifiers } = event {
+            self.expire(now);
+            self.push_key(Keybind { key, modifiers }, now);
+        }
+        self.queue.drain(..)
+    }
+
+    /// Resolves the pending sequence if it has timed out
+    ///
+    /// This should be called periodically, e.g. once per frame
+    pub fn update(&mut self, now: Instant) -> impl Iterator<Item = KeymapEvent<A>> + '_ {
+        self.expire(now);
+        self.queue.drain(..)
+    }
+
+    fn expire(&mut self, now: Instant) {
+        let (Some(timeout), Some(last)) = (self.timeout, self.last_key) else {
+            return;
+        };
+        if self.is_pending() && now.saturating_duration_since(last) >= timeout {
+            self.flush(now);
+        }
+    }
+
+    fn push_key(&mut self, keybind: Keybind, now: Instant) {
+        self.pending.push(keybind);
+        self.last_key = Some(now);
+
+        let lookup = self.lookup(&self.pending);
+        if lookup.is_prefix {
+            return;
+        }
+
+        if let Some(action) = lookup.action {
+            let ev = KeymapEvent::Action(action.clone());
+            self.queue.push_back(ev);
+            self.reset();
+            return;
+        }
+
+        // the new key doesn't continue the sequence, so resolve what we had and start over with it
+        self.pending.pop();
+        if self.pending.is_empty() {
+            self.queue.push_back(KeymapEvent::Unbound(vec![keybind]));
+            self.reset();
+            return;
+        }
+
+        self.flush(now);
+        self.push_key(keybind, now);
+    }
+
+    /// Resolves the pending sequence, the longest part of it that's bound fires and the keys after it are fed in again
+    fn flush(&mut self, now: Instant) {
+        let pending = std::mem::take(&mut self.pending);
+        self.reset();
+        if pending.is_empty() {
+            return;
+        }
+
+        let (len, ev) = (1..=pending.len())
+            .rev()
+            .find_map(|len| {
+                let action = self.lookup(&pending[..len]).action?;
+                Some((len, KeymapEvent::Action(action.clone())))
+            })
+            .unwrap_or_else(|| (1, KeymapEvent::Unbound(vec![pending[0]])));
+        self.queue.push_back(ev);
+
+        for &keybind in &pending[len..] {
+            self.push_key(keybind, now);
+        }
+    }
+
+    fn lookup(&self, sequence: &[Keybind]) -> Lookup<'_, A> {
+        self.chain()
+            .filter_map(|layer| layer.root.find(sequence))
+            .fold(
+                Lookup {
+                    action: None,
+                    is_prefix: false,
+                },
+                |lookup, node| Lookup {
+                    action: lookup.action.or(node.action.as_ref()),
+                    is_prefix: lookup.is_prefix || !node.children.is_empty(),
+                },
+            )
+    }
+
+    fn chain(&self) -> impl Iterator<Item = &Layer<M, A>> {
+        let mut next = Some(self.mode);
+        let mut seen = Vec::new();
+        std::iter::from_fn(move || {
+            let mode = next.take()?;
+            // guard against cycles in the parents
+            if seen.contains(&mode) {
+                return None;
+            }
+            seen.push(mode);
+            let layer = self.layers.get(&mode)?;
+            next = layer.parent;
+            Some(layer)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+    enum Mode {
+        Normal,
+        Insert,
+        Visual,
+    }
+
+    fn keys(sequence: &str) -> Vec<Keybind> {
+        parse_key_sequence(sequence).unwrap()
+    }
+
+    fn keymap(bindings: &[(Mode, &str, &'static str)]) -> Keymap<Mode, &'static str> {
+        let mut keymap = Keymap::new(Mode::Normal);
+        for &(mode, sequence, action) in bindings {
+            keymap.bind_str(mode, sequence, action).unwrap();
+        }
+        keymap
+    }
+
+    fn press(
+        keymap: &mut Keymap<Mode, &'static str>,
+        keybind: &str,
+        now: Instant,
+    ) -> Vec<KeymapEvent<&'static str>> {
+        let Keybind { key, modifiers } = keybind.parse().unwrap();
+        keymap
+            .process(&Event::KeyPress { key, modifiers }, now)
+            .collect()
+    }
+
+    fn keymap_with_shorter() -> Keymap<Mode, &'static str> {
+        keymap(&[
+            (Mode::Normal, "a", "a"),
+            (Mode::Normal, "a b", "ab"),
+            (Mode::Normal, "a c d", "acd"),
+            (Mode::Normal, "b", "b"),
+        ])
+    }
+
+    #[test]
+    fn prefix_then_complete() {
+        let mut keymap = keymap(&[(Mode::Normal, "g g", "top"), (Mode::Normal, "g e", "end")]);
+        let now = Instant::now();
+
+        assert_eq!(press(&mut keymap, "g", now), []);
+        assert!(keymap.is_pending());
+        assert_eq!(keymap.pending(), keys("g"));
+
+        assert_eq!(press(&mut keymap, "g", now), [KeymapEvent::Action("top")]);
+        assert!(!keymap.is_pending());
+
+        // a key which doesn't continue the sequence resolves it and starts over
+        assert_eq!(press(&mut keymap, "g", now), []);
+        assert_eq!(
+            press(&mut keymap, "x", now),
+            [
+                KeymapEvent::Unbound(keys("g")),
+                KeymapEvent::Unbound(keys("x"))
+            ]
+        );
+
+        let mut keymap = keymap_with_shorter();
+        assert_eq!(press(&mut keymap, "a", now), []);
+        assert_eq!(
+            press(&mut keymap, "x", now),
+            [KeymapEvent::Action("a"), KeymapEvent::Unbound(keys("x"))]
+        );
+    }
+
+    #[test]
+    fn timeout_flush() {
+        let mut keymap = keymap_with_shorter().timeout(Duration::from_millis(100));
+        let start = Instant::now();
+        let later = start + Duration::from_millis(100);
+
+        assert_eq!(press(&mut keymap, "a", start), []);
+        assert_eq!(keymap.update(start).collect::<Vec<_>>(), []);
+        assert_eq!(
+            keymap.update(later).collect::<Vec<_>>(),
+            [KeymapEvent::Action("a")]
+        );
+        assert!(!keymap.is_pending());
+
+        // the longest bound part fires, and the rest is fed in again
+        assert_eq!(press(&mut keymap, "a", start), []);
+        assert_eq!(press(&mut keymap, "c", start), []);
+        assert_eq!(
+            keymap.update(later).collect::<Vec<_>>(),
+            [KeymapEvent::Action("a"), KeymapEvent::Unbound(keys("c"))]
+        );
+
+        // a key after the timeout resolves the pending sequence first
+        assert_eq!(press(&mut keymap, "a", start), []);
+        assert_eq!(
+            press(&mut keymap, "b", later),
+            [KeymapEvent::Action("a"), KeymapEvent::Action("b")]
+        );
+
+        // without a timeout it waits forever
+        let mut keymap = keymap_with_shorter().timeout(None);
+        assert_eq!(press(&mut keymap, "a", start), []);
+        assert_eq!(
+            keymap
+                .update(start + Duration::from_secs(60))
+                .collect::<Vec<_>>(),
+            []
+        );
+        assert!(keymap.is_pending());
+    }
+
+    #[test]
+    fn mode_switch_resets() {
+        let mut keymap = keymap(&[(Mode::Normal, "g g", "top"), (Mode::Insert, "g", "g")]);
+        let now = Instant::now();
+
+        assert_eq!(press(&mut keymap, "g", now), []);
+        assert!(keymap.is_pending());
+
+        keymap.set_mode(Mode::Insert);
+        assert_eq!(keymap.mode(), Mode::Insert);
+        assert!(!keymap.is_pending());
+        assert_eq!(press(&mut keymap, "g", now), [KeymapEvent::Action("g")]);
+    }
+
+    #[test]
+    fn parent_fall_through() {
+        let mut keymap = keymap(&[
+            (Mode::Normal, "q", "quit"),
+            (Mode::Normal, "d d", "delete line"),
+            (Mode::Visual, "d", "delete selection"),
+            (Mode::Visual, "y", "yank"),
+        ])
+        .with_parent(Mode::Visual, Mode::Normal)
+        .with_parent(Mode::Insert, Mode::Visual);
+        let now = Instant::now();
+
+        keymap.set_mode(Mode::Insert);
+        assert_eq!(press(&mut keymap, "q", now), [KeymapEvent::Action("quit")]);
+        assert_eq!(press(&mut keymap, "y", now), [KeymapEvent::Action("yank")]);
+
+        // the child's binding is also a prefix in the parent, so it waits
+        keymap.set_mode(Mode::Visual);
+        assert_eq!(press(&mut keymap, "d", now), []);
+        assert_eq!(
+            press(&mut keymap, "d", now),
+            [KeymapEvent::Action("delete line")]
+        );
+        assert_eq!(press(&mut keymap, "d", now), []);
+        assert_eq!(
+            press(&mut keymap, "y", now),
+            [
+                KeymapEvent::Action("delete selection"),
+                KeymapEvent::Action("yank")
+            ]
+        );
+
+        // cycles don't hang
+        keymap.set_parent(Mode::Normal, Mode::Insert);
+        keymap.set_mode(Mode::Normal);
+        assert_eq!(
+            press(&mut keymap, "x", now),
+            [KeymapEvent::Unbound(keys("x"))]
+        );
+    }
+
+    #[test]
+    fn continuations() {
+        let mut keymap = keymap(&[
+            (Mode::Normal, "g", "goto"),
+            (Mode::Normal, "g g", "top"),
+            (Mode::Normal, "g e", "end"),
+            (Mode::Normal, "q", "quit"),
+            (Mode::Visual, "g e", "extend to end"),
+            (Mode::Visual, "g e x", "extend to end of line"),
+        ])
+        .with_parent(Mode::Visual, Mode::Normal);
+        let now = Instant::now();
+
+        let continuations = |keymap: &Keymap<_, _>| {
+            let mut continuations = keymap
+                .continuations()
+                .into_iter()
+                .map(|c| (c.keybind.to_string(), c.action.copied(), c.is_prefix))
+                .collect::<Vec<_>>();
+            continuations.sort();
+            continuations
+        };
+
+        assert_eq!(
+            continuations(&keymap),
+            [
+                ("g".to_string(), Some("goto"), true),
+                ("q".to_string(), Some("quit"), false),
+            ]
+        );
+
+        assert_eq!(press(&mut keymap, "g", now), []);
+        assert_eq!(
+            continuations(&keymap),
+            [
+                ("e".to_string(), Some("end"), false),
+                ("g".to_string(), Some("top"), false),
+            ]
+        );
+
+        // the child's action wins, and the parent doesn't hide that it's a prefix
+        keymap.set_mode(Mode::Visual);
+        assert_eq!(press(&mut keymap, "g", now), []);
+        assert_eq!(
+            continuations(&keymap),
+            [
+                ("e".to_string(), Some("extend to end"), true),
+                ("g".to_string(), Some("top"), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn unbind() {
+        let mut keymap = keymap(&[(Mode::Normal, "a b c", "abc"), (Mode::Normal, "a d", "ad")]);
+        let now = Instant::now();
+
+        assert_eq!(keymap.unbind(Mode::Normal, &keys("a b")), None);
+        assert_eq!(keymap.unbind(Mode::Insert, &keys("a b c")), None);
+        assert_eq!(keymap.unbind(Mode::Normal, &keys("a b c")), Some("abc"));
+        assert_eq!(keymap.unbind(Mode::Normal, &keys("a b c")), None);
+
+        // nothing is left under `a b`, so it's no longer a prefix
+        assert_eq!(press(&mut keymap, "a", now), []);
+        assert_eq!(
+            press(&mut keymap, "b", now),
+            [
+                KeymapEvent::Unbound(keys("a")),
+                KeymapEvent::Unbound(keys("b"))
+            ]
+        );
+
+        assert_eq!(keymap.unbind(Mode::Normal, &keys("a d")), Some("ad"));
+        assert!(keymap.continuations().is_empty());
+        assert_eq!(
+            press(&mut keymap, "a", now),
+            [KeymapEvent::Unbound(keys("a"))]
+        );
+    }
+}
@@ -1,8 +1,47 @@
-use std::{collections::VecDeque, thread::JoinHandle};
+use std::{
+    collections::VecDeque,
+    io::Write as _,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
 
 use mars_math::{Delta, Position, Size};
 use termina::Terminal as _;
 
+mod keybind;
+pub use keybind::ParseKeybindError;
+
+mod keymap;
+pub use keymap::{Continuation, Keymap, KeymapEvent, parse_key_sequence};
+
+mod hover;
+pub use hover::{HoverRegions, RegionId};
+
+mod clipboard;
+pub use clipboard::{Clipboard, ClipboardSelection};
+
+mod viewport;
+use viewport::Viewport;
+
+mod backend;
+pub use backend::Backend;
+
+mod mock;
+pub use mock::MockTerminal;
+
+mod recording;
+pub use recording::{ParseRecordingError, Recorder, Recording, Replay};
+
+mod asciicast;
+pub use asciicast::AsciicastRecorder;
+
+/// How long to wait for the terminal to report the cursor's position
+const CURSOR_REPORT_TIMEOUT: Duration = Duration::from_millis(500);
+
 const fn set(f: termina::escape::csi::DecPrivateModeCode) -> termina::escape::csi::Csi {
     termina::escape::csi::Csi::Mode(termina::escape::csi::Mode::SetDecPrivateMode(
         termina::escape::csi::DecPrivateMode::Code(f),
@@ -15,13 +54,68 @@ const fn reset(f: termina::escape::csi::DecPrivateModeCode) -> termina::escape::
     ))
 }
 
+const fn push_keyboard_flags() -> termina::escape::csi::Csi {
+    use termina::escape::csi::KittyKeyboardFlags as Flags;
+    termina::escape::csi::Csi::Keyboard(termina::escape::csi::Keyboard::PushFlags(
+        Flags::DISAMBIGUATE_ESCAPE_CODES
+            .union(Flags::REPORT_EVENT_TYPES)
+            // so shift+a is reported as `A` rather than `a` with shift held
+            .union(Flags::REPORT_ALTERNATE_KEYS)
+            .union(Flags::REPORT_ALL_KEYS_AS_ESCAPE_CODES),
+    ))
+}
+
+const fn pop_keyboard_flags() -> termina::escape::csi::Csi {
+    termina::escape::csi::Csi::Keyboard(termina::escape::csi::Keyboard::PopFlags(1))
+}
+
+const fn default_cursor_style() -> termina::escape::csi::Csi {
+    termina::escape::csi::Csi::Cursor(termina::escape::csi::Cursor::CursorStyle(
+        termina::style::CursorStyle::Default,
+    ))
+}
+
+fn push_title() -> termina::escape::csi::Csi {
+    termina::escape::csi::Csi::Window(Box::new(
+        termina::escape::csi::Window::PushIconAndWindowTitle,
+    ))
+}
+
+fn pop_title() -> termina::escape::csi::Csi {
+    termina::escape::csi::Csi::Window(Box::new(
+        termina::escape::csi::Window::PopIconAndWindowTitle,
+    ))
+}
+
+fn move_to_row(row: u32) -> termina::escape::csi::Csi {
+    termina::escape::csi::Csi::Cursor(termina::escape::csi::Cursor::Position {
+        line: termina::OneBased::from_zero_based(row as _),
+        col: termina::OneBased::from_zero_based(0),
+    })
+}
+
+// control characters would terminate the OSC early
+fn sanitize_title(title: &str) -> String {
+    title.chars().filter(|c| !c.is_control()).collect()
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Config {
     pub(crate) hide_cursor: bool,
     pub(crate) mouse_capture: bool,
     pub(crate) ctrl_c_quits: bool,
+    pub(crate) ctrl_z_suspends: bool,
     pub(crate) use_alt_screen: bool,
     pub(crate) hook_panics: bool,
+    pub(crate) kitty_keyboard: bool,
+    pub(crate) bracketed_paste: bool,
+    pub(crate) focus_reporting: bool,
+    pub(crate) click_interval: Duration,
+    pub(crate) click_tolerance: u32,
+    pub(crate) pixel_mouse: bool,
+    pub(crate) restore_title: bool,
+    pub(crate) reset_cursor_shape: bool,
+    pub(crate) inline_height: Option<u32>,
 }
 
 impl Default for Config {
@@ -36,8 +130,18 @@ impl Config {
             hide_cursor: true,
             mouse_capture: true,
             ctrl_c_quits: true,
+            ctrl_z_suspends: false,
             use_alt_screen: true,
             hook_panics: true,
+            kitty_keyboard: false,
+            bracketed_paste: false,
+            focus_reporting: false,
+            click_interval: Duration::from_millis(500),
+            click_tolerance: 1,
+            pixel_mouse: false,
+            restore_title: true,
+            reset_cursor_shape: true,
+            inline_height: None,
         }
     }
 
@@ -56,6 +160,15 @@ impl Config {
         self
     }
 
+    /// Turn Ctrl-Z and SIGTSTP into an [`Event::Suspend`], like Ctrl-Z would suspend a program outside of raw mode
+    ///
+    /// This is off by default, Ctrl-Z is then just a key and a SIGTSTP stops the process without resetting the terminal.
+    /// This only applies on unix, where there's job control
+    pub const fn ctrl_z_suspends(mut self, ctrl_z_suspends: bool) -> Self {
+        self.ctrl_z_suspends = ctrl_z_suspends;
+        self
+    }
+
     pub const fn use_alt_screen(mut self, use_alt_screen: bool) -> Self {
         self.use_alt_screen = use_alt_screen;
         self
@@ -65,6 +178,71 @@ impl Config {
         self.hook_panics = hook_panics;
         self
     }
+
+    /// Opt in to the kitty progressive keyboard enhancement
+    ///
+    /// This lets the terminal report key repeats, key releases and presses of the modifier keys themselves
+    pub const fn kitty_keyboard(mut self, kitty_keyboard: bool) -> Self {
+        self.kitty_keyboard = kitty_keyboard;
+        self
+    }
+
+    /// Have pasted text delivered as a single [`Event::Paste`] rather than as individual key presses
+    pub const fn bracketed_paste(mut self, bracketed_paste: bool) -> Self {
+        self.bracketed_paste = bracketed_paste;
+        self
+    }
+
+    /// Report when the terminal window gains or loses focus with [`Event::FocusGained`] and [`Event::FocusLost`]
+    pub const fn focus_reporting(mut self, focus_reporting: bool) -> Self {
+        self.focus_reporting = focus_reporting;
+        self
+    }
+
+    /// The longest time between clicks for them to count towards an [`Event::MouseClick`] `count`
+    pub const fn click_interval(mut self, click_interval: Duration) -> Self {
+        self.click_interval = click_interval;
+        self
+    }
+
+    /// How many cells the mouse can move between clicks for them to count towards an [`Event::MouseClick`] `count`
+    pub const fn click_tolerance(mut self, click_tolerance: u32) -> Self {
+        self.click_tolerance = click_tolerance;
+        self
+    }
+
+    /// Report mouse positions in pixels (SGR-Pixels), mouse events will then carry a [`PixelPosition`]
+    ///
+    /// This is only enabled if the terminal reports its size in pixels, as that is needed to find the cell under the mouse
+    pub const fn pixel_mouse(mut self, pixel_mouse: bool) -> Self {
+        self.pixel_mouse = pixel_mouse;
+        self
+    }
+
+    /// Save the window title and icon name on startup and restore them when the terminal is reset
+    pub const fn restore_title(mut self, restore_title: bool) -> Self {
+        self.restore_title = restore_title;
+        self
+    }
+
+    /// Reset the cursor's shape when the terminal is reset, in case the application changed it
+    ///
+    /// The shape from before can't be queried, so this sends DECSCUSR 0 which gives the shape configured in the terminal.
+    /// Turn it off to leave the cursor as it is, e.g. when the shell sets its own shape and the application never changes it
+    pub const fn reset_cursor_shape(mut self, reset_cursor_shape: bool) -> Self {
+        self.reset_cursor_shape = reset_cursor_shape;
+        self
+    }
+
+    /// Draw inline, in `height` lines below the cursor, rather than on the alternate screen
+    ///
+    /// The lines above the viewport are left alone and stay in the scrollback, more can be printed there with
+    /// [`Terminal::insert_before`]. The last frame is left in place when the terminal is reset
+    pub const fn inline(mut self, height: u32) -> Self {
+        self.inline_height = Some(height);
+        self.use_alt_screen = false;
+        self
+    }
 }
 
 pub struct Terminal {
@@ -72,6 +250,15 @@ pub struct Terminal {
     events: std::sync::mpsc::Receiver<Event>,
     size: Size,
     config: Config,
+    hover_regions: HoverRegions,
+    clipboard: Clipboard,
+    title_depth: Arc<AtomicUsize>,
+    viewport: Viewport,
+    #[cfg(unix)]
+    signals: signal_hook::iterator::Handle,
+    // the SIGCONT after our own suspend, which has already set the terminal up again
+    #[cfg(unix)]
+    suspended: bool,
     _handle: JoinHandle<()>,
 }
 
@@ -81,19 +268,112 @@ impl Terminal {
         let mut terminal = termina::PlatformTerminal::new()?;
         terminal.enter_raw_mode()?;
 
-        let termina::WindowSize { cols, rows, .. } = terminal.get_dimensions()?;
-        let size = Size::new(cols as _, rows as _);
+        let dimensions = terminal.get_dimensions()?;
+        let screen = Size::new(dimensions.cols as u32, dimensions.rows as u32);
+
+        let cell_size = cell_size(&dimensions);
+        let config = config
+            .pixel_mouse(config.pixel_mouse && cell_size.is_some())
+            .use_alt_screen(config.use_alt_screen && config.inline_height.is_none());
+
+        let viewport = match config.inline_height {
+            Some(height) => {
+                let height = height.clamp(1, screen.height.max(1));
+                let top = Self::reserve_lines(&mut terminal, height, screen)?;
+                Viewport::inline(top, height)
+            }
+            None => Viewport::fullscreen(),
+        };
+        let size = viewport.fit(screen);
 
         Self::initialize(&mut terminal, config)?;
 
+        let title_depth = Arc::new(AtomicUsize::new(0));
+
+        if config.hook_panics {
+            let viewport = viewport.clone();
+            let title_depth = title_depth.clone();
+            terminal.set_panic_hook(move |out| {
+                Self::unwind_titles(&title_depth, out);
+                Self::reset(config, &viewport, out)
+            });
+        }
+
+        // a SIGTSTP from outside (e.g. `kill -TSTP`) suspends just like Ctrl-Z,
+        // and a SIGCONT means something else stopped us and the terminal has to be set up again
+        #[cfg(unix)]
+        let signals = {
+            use signal_hook::consts::{SIGCONT, SIGTSTP};
+
+            let mut signals = signal_hook::iterator::Signals::new([SIGCONT])?;
+            if config.ctrl_z_suspends {
+                signals.add_signal(SIGTSTP)?;
+            }
+            let handle = signals.handle();
+            let tx = tx.clone();
+            std::thread::spawn(move || {
+                for signal in signals.forever() {
+                    let ev = match signal {
+                        SIGTSTP => Event::Suspend,
+                        _ => Event::Resume,
+                    };
+                    if tx.send(ev).is_err() {
+                        break;
+                    }
+                }
+            });
+            handle
+        };
+
+        let hover_regions = HoverRegions::default();
+        let clipboard = Clipboard::default();
+
         let reader = terminal.event_reader();
         let _handle = std::thread::spawn({
+            let hover_regions = hover_regions.clone();
+            let clipboard = clipboard.clone();
+            let viewport = viewport.clone();
             move || {
                 const CTRL_C: Keybind = Keybind::char('c').control();
+                const CTRL_Z: Keybind = Keybind::char('z').control();
+
+                let mut state = EventState::new(
+                    config,
+                    cell_size,
+                    screen,
+                    hover_regions,
+                    clipboard,
+                    viewport,
+                );
+                'outer: loop {
+                    // a partial OSC reply is only waited on for so long
+                    let ready = match state.osc_deadline() {
+                        Some(deadline) => reader.poll(
+                            Some(deadline.saturating_duration_since(Instant::now())),
+                            |_| true,
+                        ),
+                        None => Ok(true),
+                    };
+                    let events = match ready {
+                        Ok(true) => match reader.read(|_| true) {
+                            Ok(ev) => state.translate(&ev, Instant::now()),
+                            Err(..) => break,
+                        },
+                        Ok(false) => state.tick(Instant::now()),
+                        Err(..) => break,
+                    };
+
+                    for ev in events {
+                        let ev = match ev {
+                            ev if cfg!(unix)
+                                && config.ctrl_z_suspends
+                                && ev.is_keybind(&CTRL_Z) =>
+                            {
+                                Event::Suspend
+                            }
+                            ev => ev,
+                        };
 
-                let mut state = EventState::default();
-                'outer: while let Ok(ev) = reader.read(|_| true) {
-                    for ev in state.translate(&ev) {
                         let mut was_quit = ev.is_quit();
                         if config.ctrl_c_quits {
                             was_quit ^= ev.is_keybind(&CTRL_C)
@@ -118,16 +398,200 @@ impl Terminal {
             events,
             size,
             config,
+            hover_regions,
+            clipboard,
+            title_depth,
+            viewport,
+            #[cfg(unix)]
+            signals,
+            #[cfg(unix)]
+            suspended: false,
             _handle,
         })
     }
 
+    /// The size of the area the application draws to, for an inline viewport this is only part of the screen
     pub const fn size(&self) -> Size {
         self.size
     }
 
+    /// The size of the whole screen, this is the same as [`Terminal::size`] unless the viewport is [`Config::inline`]
+    pub fn screen_size(&self) -> Size {
+        self.terminal
+            .get_dimensions()
+            .map_or(self.size, |dimensions| {
+                Size::new(dimensions.cols as u32, dimensions.rows as u32)
+            })
+    }
+
+    /// Where the area the application draws to starts on the screen
+    ///
+    /// This is only non-zero with [`Config::inline`], and can change when the terminal is resized
+    pub fn origin(&self) -> Position {
+        self.viewport.origin()
+    }
+
+    /// A handle to the regions which produce [`Event::MouseEnter`] and [`Event::MouseLeave`]
+    pub fn hover_regions(&self) -> HoverRegions {
+        self.hover_regions.clone()
+    }
+
+    /// A handle for queueing clipboard requests, these are sent with [`Terminal::flush_clipboard`]
+    pub fn clipboard(&self) -> Clipboard {
+        self.clipboard.clone()
+    }
+
+    /// Sends any requests queued on the [`Clipboard`] handles
+    pub fn flush_clipboard(&mut self) -> std::io::Result<()> {
+        use clipboard::ClipboardRequest as R;
+        for request in self.clipboard.drain() {
+            match request {
+                R::Copy(selection, text) => self.set_clipboard(selection, &text)?,
+                R::Read(selection) => self.request_clipboard(selection)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies the text to the selection (OSC 52)
+    ///
+    /// This fails if the text is longer than [`Clipboard::MAX_LEN`]
+    pub fn set_clipboard(
+        &mut self,
+        selection: ClipboardSelection,
+        text: &str,
+    ) -> std::io::Result<()> {
+        clipboard::write_copy(self, selection, text)?;
+        self.flush()
+    }
+
+    /// Asks the terminal for the contents of the selection (OSC 52)
+    ///
+    /// If the terminal allows it, the reply arrives as an [`Event::Clipboard`]
+    pub fn request_clipboard(&mut self, selection: ClipboardSelection) -> std::io::Result<()> {
+        clipboard::write_read(self, selection)?;
+        self.clipboard.expect_reply();
+        self.flush()
+    }
+
+    /// Sets the window title (OSC 2)
+    pub fn set_title(&mut self, title: &str) -> std::io::Result<()> {
+        let title = sanitize_title(title);
+        let osc = termina::escape::osc::Osc::SetWindowTitle(&title);
+        write!(self, "{osc}")?;
+        self.flush()
+    }
+
+    /// Sets the icon name (OSC 1), some terminals show this as the tab title
+    pub fn set_icon_name(&mut self, name: &str) -> std::io::Result<()> {
+        let name = sanitize_title(name);
+        let osc = termina::escape::osc::Osc::SetIconName(&name);
+        write!(self, "{osc}")?;
+        self.flush()
+    }
+
+    /// Sets both the window title and the icon name (OSC 0)
+    pub fn set_title_and_icon_name(&mut self, title: &str) -> std::io::Result<()> {
+        let title = sanitize_title(title);
+        let osc = termina::escape::osc::Osc::SetIconNameAndWindowTitle(&title);
+        write!(self, "{osc}")?;
+        self.flush()
+    }
+
+    /// Saves the current window title and icon name on the terminal's title stack
+    pub fn push_title(&mut self) -> std::io::Result<()> {
+        write!(self, "{}", push_title())?;
+        self.title_depth.fetch_add(1, Ordering::SeqCst);
+        self.flush()
+    }
+
+    /// Restores the window title and icon name saved by [`Terminal::push_title`]
+    pub fn pop_title(&mut self) -> std::io::Result<()> {
+        if self.title_depth.load(Ordering::SeqCst) == 0 {
+            return Ok(());
+        }
+        write!(self, "{}", pop_title())?;
+        self.title_depth.fetch_sub(1, Ordering::SeqCst);
+        self.flush()
+    }
+
+    /// Pops every title the application pushed, to get back to the title from startup
+    fn unwind_titles(title_depth: &AtomicUsize, terminal: &mut dyn std::io::Write) {
+        for _ in 0..title_depth.swap(0, Ordering::SeqCst) {
+            _ = write!(terminal, "{}", pop_title());
+        }
+        _ = terminal.flush();
+    }
+
+    /// Prints `lines` above an inline viewport, moving the viewport down to make room for them
+    ///
+    /// Once the viewport reaches the bottom of the screen, the lines above it scroll into the scrollback.
+    /// Control characters are removed and each line should fit in the screen's width, a line that wraps
+    /// overwrites the viewport. Nothing is printed if the viewport fills the screen.
+    ///
+    /// This fails if the terminal isn't [`Config::inline`]
+    pub fn insert_before<S: AsRef<str>>(
+        &mut self,
+        lines: impl IntoIterator<Item = S>,
+    ) -> std::io::Result<()> {
+        use termina::escape::csi::{Csi, Edit, Sgr};
+
+        let Some(height) = self.viewport.height() else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "lines can only be inserted before an inline viewport",
+            ));
+        };
+
+        let screen = self.terminal.get_dimensions()?.rows as u32;
+        let height = height.min(screen);
+        let room = (screen - height) as usize;
+        if room == 0 {
+            return Ok(());
+        }
+
+        let lines = lines
+            .into_iter()
+            .map(|line| {
+                let line = line.as_ref().chars().filter(|c| !c.is_control());
+                line.collect::<String>()
+            })
+            .collect::<Vec<_>>();
+
+        for chunk in lines.chunks(room) {
+            let n = chunk.len() as u32;
+            let top = self.viewport.origin().y as u32;
+
+            // make room below the viewport, the newlines push the top of the screen into the scrollback
+            let scroll = (top + height + n).saturating_sub(screen);
+            if scroll > 0 {
+                let newlines = "\n".repeat(scroll as usize);
+                write!(self, "{}{newlines}", move_to_row(screen - 1))?;
+            }
+
+            // inserting lines at the top of the viewport pushes it down, and leaves blank lines for the text
+            let top = top - scroll;
+            write!(
+                self,
+                "{}{}{}",
+                move_to_row(top),
+                Csi::Edit(Edit::InsertLine(n)),
+                Csi::Sgr(Sgr::Reset)
+            )?;
+            for (row, line) in (top..).zip(chunk) {
+                write!(self, "{}{line}", move_to_row(row))?;
+            }
+
+            self.viewport.set_top(top + n);
+        }
+
+        self.flush()
+    }
+
     pub fn try_read_event(&mut self) -> Option<Event> {
         match self.events.try_recv() {
+            #[cfg(unix)]
+            Ok(Event::Resume) if std::mem::take(&mut self.suspended) => self.try_read_event(),
             Ok(ev) => {
                 if let Event::Resize { size } = ev {
                     self.size = size;
@@ -139,7 +603,110 @@ impl Terminal {
         }
     }
 
+    /// Stops the process, like Ctrl-Z would outside of raw mode
+    ///
+    /// The terminal is reset while the process is in the background, and set up again once it is continued (SIGCONT).
+    /// This returns after the process is continued, the application should redraw everything.
+    ///
+    /// Titles pushed with [`Terminal::push_title`] are discarded. This does nothing on platforms without job control
+    pub fn suspend(&mut self) -> std::io::Result<()> {
+        #[cfg(unix)]
+        {
+            while self.title_depth.load(Ordering::SeqCst) > 0 {
+                self.pop_title()?;
+            }
+
+            let viewport = self.viewport.clone();
+            Self::reset(self.config, &viewport, self);
+            self.terminal.enter_cooked_mode()?;
+
+            // the process stops here until it's continued
+            signal_hook::low_level::emulate_default_handler(signal_hook::consts::SIGTSTP)?;
+            self.suspended = true;
+
+            self.resume()?;
+            Self::push_stacks(&mut self.terminal, self.config)?;
+        }
+        Ok(())
+    }
+
+    /// Sets the terminal up again after the process was stopped and continued from outside, see [`Event::Resume`]
+    ///
+    /// The keyboard flags and titles stay on the terminal's stacks, so only the modes are set again.
+    /// The application should redraw everything afterwards
+    pub fn resume(&mut self) -> std::io::Result<()> {
+        #[cfg(unix)]
+        {
+            self.terminal.enter_raw_mode()?;
+            let dimensions = self.terminal.get_dimensions()?;
+            let screen = Size::new(dimensions.cols as u32, dimensions.rows as u32);
+
+            // the shell has moved the cursor, the event thread moves the viewport once the terminal reports where to
+            if let Some(height) = self.viewport.height() {
+                Self::request_lines(&mut self.terminal, height)?;
+            }
+
+            Self::set_modes(&mut self.terminal, self.config)?;
+            self.size = self.viewport.fit(screen);
+        }
+        Ok(())
+    }
+
+    /// Scrolls the screen so there are `height` free lines from the cursor down,
+    /// and asks the terminal where the cursor ended up
+    fn request_lines(terminal: &mut impl termina::Terminal, height: u32) -> std::io::Result<()> {
+        use termina::escape::csi::{Csi, Cursor};
+
+        // the newlines scroll the screen if we're too close to the bottom
+        let newlines = "\n".repeat(height as usize - 1);
+        write!(terminal, "\r{newlines}")?;
+        write!(
+            terminal,
+            "{}",
+            Csi::Cursor(Cursor::RequestActivePositionReport)
+        )?;
+        terminal.flush()
+    }
+
+    /// Reserves `height` lines from the cursor down, returning the first of those lines
+    fn reserve_lines(
+        terminal: &mut impl termina::Terminal,
+        height: u32,
+        screen: Size,
+    ) -> std::io::Result<u32> {
+        use termina::escape::csi::{Csi, Cursor};
+
+        Self::request_lines(terminal, height)?;
+
+        let is_report = |ev: &termina::Event| {
+            matches!(
+                ev,
+                termina::Event::Csi(Csi::Cursor(Cursor::ActivePositionReport { .. }))
+            )
+        };
+
+        let reader = terminal.event_reader();
+        let bottom = match reader.poll(Some(CURSOR_REPORT_TIMEOUT), is_report)? {
+            true => match reader.read(is_report)? {
+                termina::Event::Csi(Csi::Cursor(Cursor::ActivePositionReport { line, .. })) => {
+                    line.get_zero_based() as u32
+                }
+                _ => unreachable!("the filter only accepts position reports"),
+            },
+            // the terminal didn't answer, the bottom of the screen is the best guess
+            false => screen.height.saturating_sub(1),
+        };
+
+        Ok((bottom + 1).saturating_sub(height))
+    }
+
     fn initialize(terminal: &mut impl termina::Terminal, config: Config) -> std::io::Result<()> {
+        Self::set_modes(terminal, config)?;
+        Self::push_stacks(terminal, config)
+    }
+
+    /// Sets the modes, which can be done again without undoing anything
+    fn set_modes(terminal: &mut impl termina::Terminal, config: Config) -> std::io::Result<()> {
         use termina::escape::csi::DecPrivateModeCode as Dec;
 
         if config.use_alt_screen {
@@ -150,6 +717,14 @@ impl Terminal {
             write!(terminal, "{}", reset(Dec::ShowCursor))?;
         }
 
+        if config.bracketed_paste {
+            write!(terminal, "{}", set(Dec::BracketedPaste))?;
+        }
+
+        if config.focus_reporting {
+            write!(terminal, "{}", set(Dec::FocusTracking))?;
+        }
+
         if config.mouse_capture {
             for mouse in [
                 Dec::MouseTracking,
@@ -160,21 +735,55 @@ impl Terminal {
             ] {
                 write!(terminal, "{}", set(mouse))?;
             }
+
+            if config.pixel_mouse {
+                write!(terminal, "{}", set(Dec::SGRPixelsMouse))?;
+            }
         }
 
-        if config.hook_panics {
-            terminal.set_panic_hook(move |out| Self::reset(config, out));
+        terminal.flush()
+    }
+
+    /// Pushes onto the terminal's stacks, which `reset` pops
+    fn push_stacks(terminal: &mut impl termina::Terminal, config: Config) -> std::io::Result<()> {
+        // the flags are kept per-screen, so this has to be after entering the alt screen
+        if config.kitty_keyboard {
+            write!(terminal, "{}", push_keyboard_flags())?;
         }
 
-        terminal.flush()?;
+        if config.restore_title {
+            write!(terminal, "{}", push_title())?;
+        }
 
-        Ok(())
+        terminal.flush()
     }
 
-    fn reset(config: Config, terminal: &mut dyn std::io::Write) {
+    fn reset(config: Config, viewport: &Viewport, terminal: &mut dyn std::io::Write) {
         use termina::escape::csi::DecPrivateModeCode as Dec;
 
+        // leave the last frame where it is and continue on the line below it
+        if let Some(row) = viewport.last_row() {
+            _ = write!(
+                terminal,
+                "{}{}\r\n",
+                move_to_row(row),
+                termina::escape::csi::Csi::Sgr(termina::escape::csi::Sgr::Reset)
+            );
+            _ = terminal.flush();
+        }
+
+        // the flags are kept per-screen, so pop them before we leave the alt screen
+        if config.kitty_keyboard {
+            _ = write!(terminal, "{}", pop_keyboard_flags());
+            _ = terminal.flush();
+        }
+
         if config.mouse_capture {
+            if config.pixel_mouse {
+                _ = write!(terminal, "{}", reset(Dec::SGRPixelsMouse));
+                _ = terminal.flush();
+            }
+
             for mouse in [
                 Dec::MouseTracking,
                 Dec::ButtonEventMouse,
@@ -187,6 +796,16 @@ impl Terminal {
             }
         }
 
+        if config.bracketed_paste {
+            _ = write!(terminal, "{}", reset(Dec::BracketedPaste));
+            _ = terminal.flush();
+        }
+
+        if config.focus_reporting {
+            _ = write!(terminal, "{}", reset(Dec::FocusTracking));
+            _ = terminal.flush();
+        }
+
         if config.use_alt_screen {
             _ = write!(terminal, "{}", reset(Dec::ClearAndEnableAlternateScreen));
             _ = terminal.flush();
@@ -196,6 +815,17 @@ impl Terminal {
             _ = write!(terminal, "{}", set(Dec::ShowCursor));
             _ = terminal.flush();
         }
+
+        // the application may have changed the cursor's shape
+        if config.reset_cursor_shape {
+            _ = write!(terminal, "{}", default_cursor_style());
+            _ = terminal.flush();
+        }
+
+        if config.restore_title {
+            _ = write!(terminal, "{}", pop_title());
+            _ = terminal.flush();
+        }
     }
 }
 
@@ -213,8 +843,15 @@ impl std::io::Write for Terminal {
 
 impl Drop for Terminal {
     fn drop(&mut self) {
-        Self::reset(self.config, self);
+        // unwind anything the application pushed so we get back to the title from startup
+        let title_depth = self.title_depth.clone();
+        Self::unwind_titles(&title_depth, self);
+        let viewport = self.viewport.clone();
+        Self::reset(self.config, &viewport, self);
         _ = self.terminal.enter_cooked_mode();
+
+        #[cfg(unix)]
+        self.signals.close();
     }
 }
 
@@ -224,9 +861,20 @@ pub enum Event {
         key: Key,
         modifiers: KeyModifiers,
     },
+    /// A held key was repeated, this is only reported with [`Config::kitty_keyboard`]
+    KeyRepeat {
+        key: Key,
+        modifiers: KeyModifiers,
+    },
+    /// A key was released, this is only reported with [`Config::kitty_keyboard`]
+    KeyRelease {
+        key: Key,
+        modifiers: KeyModifiers,
+    },
     MouseMove {
         pos: Position,
         modifiers: KeyModifiers,
+        pixel: Option<PixelPosition>,
     },
     MouseScroll {
         delta: Delta<i32>,
@@ -236,6 +884,7 @@ pub enum Event {
         modifiers: KeyModifiers,
         pos: Position,
         down: bool,
+        pixel: Option<PixelPosition>,
     },
     MouseDragHeld {
         button: MouseButton,
@@ -243,16 +892,57 @@ pub enum Event {
         origin: Position,
         pos: Position,
         delta: Delta<i32>,
+        pixel: Option<PixelPosition>,
     },
     MouseDragRelease {
         button: MouseButton,
         modifiers: KeyModifiers,
         origin: Position,
         pos: Position,
+        pixel: Option<PixelPosition>,
+    },
+    /// A button was pressed and released without dragging
+    ///
+    /// `count` is 2 for a double click, 3 for a triple click and so on, see [`Config::click_interval`]
+    MouseClick {
+        button: MouseButton,
+        modifiers: KeyModifiers,
+        count: u8,
+        pos: Position,
+        pixel: Option<PixelPosition>,
+    },
+    /// The mouse moved into a region registered with [`HoverRegions`]
+    MouseEnter {
+        region: RegionId,
+        pos: Position,
+    },
+    /// The mouse moved out of a region registered with [`HoverRegions`]
+    MouseLeave {
+        region: RegionId,
+        pos: Position,
     },
     Resize {
         size: Size,
     },
+    /// Text pasted into the terminal, this is only reported with [`Config::bracketed_paste`]
+    Paste(String),
+    /// The terminal window gained focus, this is only reported with [`Config::focus_reporting`]
+    FocusGained,
+    /// The terminal window lost focus, this is only reported with [`Config::focus_reporting`]
+    FocusLost,
+    /// The reply to [`Terminal::request_clipboard`]
+    Clipboard {
+        selection: ClipboardSelection,
+        contents: String,
+    },
+    /// The user asked to suspend the process, with Ctrl-Z or a SIGTSTP
+    ///
+    /// Call [`Terminal::suspend`] to go along with it, this is only reported with [`Config::ctrl_z_suspends`]
+    Suspend,
+    /// The process was continued after something else stopped it, e.g. `kill -STOP` and then `fg`
+    ///
+    /// Call [`Terminal::resume`] to set the terminal up again
+    Resume,
     Quit,
 }
 
@@ -261,8 +951,12 @@ impl Event {
         matches!(self, Self::Quit)
     }
 
+    /// Does this event trigger the [`Keybind`]?
+    ///
+    /// Repeats are treated as presses, so a held key behaves the same with or without [`Config::kitty_keyboard`]
     pub fn is_keybind(&self, keybind: &Keybind) -> bool {
-        let &Self::KeyPress { key, modifiers } = self else {
+        let (&Self::KeyPress { key, modifiers } | &Self::KeyRepeat { key, modifiers }) = self
+        else {
             return false;
         };
         Keybind { key, modifiers } == *keybind
@@ -283,54 +977,315 @@ enum DragState {
     None,
 }
 
-#[derive(Debug, Default)]
+/// An OSC reply that's being collected from key events, see [`EventState::capture_osc`]
+#[derive(Debug)]
+struct OscReply {
+    keys: Vec<termina::event::KeyEvent>,
+    body: String,
+    last_key: Instant,
+}
+
+impl OscReply {
+    /// How long to wait for the next key of a reply before giving up on it
+    const TIMEOUT: Duration = Duration::from_millis(100);
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Click {
+    button: MouseButton,
+    pos: Position,
+    at: Instant,
+    count: u8,
+}
+
+#[derive(Debug)]
 struct EventState {
     pos: Position,
     drag_state: DragState,
+    pressed: Option<(MouseButton, Position)>,
+    last_click: Option<Click>,
+    click_interval: Duration,
+    click_tolerance: u32,
+    pixel_mouse: bool,
+    cell_size: Option<Size>,
+    screen: Size,
+    hover_regions: HoverRegions,
+    hovered: Vec<RegionId>,
+    clipboard: Clipboard,
+    osc: Option<OscReply>,
+    viewport: Viewport,
     queue: VecDeque<Event>,
 }
 
 impl EventState {
-    fn translate(&mut self, event: &termina::event::Event) -> impl IntoIterator<Item = Event> {
-        self.process(event);
+    fn new(
+        config: Config,
+        cell_size: Option<Size>,
+        screen: Size,
+        hover_regions: HoverRegions,
+        clipboard: Clipboard,
+        viewport: Viewport,
+    ) -> Self {
+        Self {
+            pos: Position::ZERO,
+            drag_state: DragState::default(),
+            pressed: None,
+            last_click: None,
+            click_interval: config.click_interval,
+            click_tolerance: config.click_tolerance,
+            pixel_mouse: config.pixel_mouse,
+            cell_size,
+            screen,
+            hover_regions,
+            hovered: Vec::new(),
+            clipboard,
+            osc: None,
+            viewport,
+            queue: VecDeque::new(),
+        }
+    }
+
+    fn translate(
+        &mut self,
+        event: &termina::event::Event,
+        now: Instant,
+    ) -> std::collections::vec_deque::Drain<'_, Event> {
+        self.expire_osc(now);
+        self.process(event, now);
         self.queue.drain(..)
     }
 
-    fn process(&mut self, event: &termina::event::Event) {
+    /// Called when no event arrived before [`EventState::osc_deadline`]
+    fn tick(&mut self, now: Instant) -> std::collections::vec_deque::Drain<'_, Event> {
+        self.expire_osc(now);
+        self.queue.drain(..)
+    }
+
+    fn process(&mut self, event: &termina::event::Event, now: Instant) {
         match event {
-            &termina::Event::Key(ke) => self.translate_key(ke),
-            &termina::Event::Mouse(me) => self.translate_mouse(me),
-            &termina::Event::WindowResized(termina::WindowSize { rows, cols, .. }) => {
-                let size = Size::new(cols as u32, rows as u32);
+            &termina::Event::Key(ke) => self.translate_key(ke, now),
+            &termina::Event::Mouse(me) => self.translate_mouse(me, now),
+            termina::Event::WindowResized(window_size) => {
+                // the font size may have changed
+                if let Some(cell_size) = cell_size(window_size) {
+                    self.cell_size = Some(cell_size);
+                }
+                self.screen = Size::new(window_size.cols as u32, window_size.rows as u32);
+                let size = self.viewport.fit(self.screen);
+                self.queue.push_back(Event::Resize { size });
+            }
+            // an inline viewport is placed wherever the terminal says the cursor is
+            termina::Event::Csi(termina::escape::csi::Csi::Cursor(
+                termina::escape::csi::Cursor::ActivePositionReport { line, .. },
+            )) if self.viewport.height().is_some() => {
+                self.viewport.set_bottom(line.get_zero_based() as u32);
+                let size = self.viewport.fit(self.screen);
                 self.queue.push_back(Event::Resize { size });
             }
-            termina::Event::FocusIn | termina::Event::FocusOut | termina::Event::Paste(_) => {}
+            termina::Event::Paste(data) => {
+                self.queue.push_back(Event::Paste(data.clone()));
+            }
+            termina::Event::FocusIn => self.queue.push_back(Event::FocusGained),
+            termina::Event::FocusOut => self.queue.push_back(Event::FocusLost),
             _ => {}
         }
     }
 
-    fn translate_key(&mut self, ke: termina::event::KeyEvent) {
-        if !matches!(ke.kind, termina::event::KeyEventKind::Press) {
-            return;
+    fn translate_key(&mut self, ke: termina::event::KeyEvent, now: Instant) {
+        if !self.capture_osc(ke, now) {
+            self.push_key(ke);
         }
+    }
+
+    /// Collects the OSC 52 reply to [`Terminal::request_clipboard`]
+    ///
+    /// termina doesn't parse OSC sequences, so the reply arrives as keys: `ESC ]` is alt+`]`,
+    /// the body is plain characters and `ESC \` or BEL (alt+`\` or ctrl+g) ends it.
+    /// Anything that turns out not to be a reply, or stops short for [`OscReply::TIMEOUT`], is passed on as the keys it was
+    fn capture_osc(&mut self, ke: termina::event::KeyEvent, now: Instant) -> bool {
+        use termina::event::{KeyCode, KeyEventKind, Modifiers};
 
-        let Some(key) = Key::from_termina(ke.code) else {
+        if !matches!(ke.kind, KeyEventKind::Press) {
+            return false;
+        }
+
+        let Some(reply) = &mut self.osc else {
+            let is_start = matches!(ke.code, KeyCode::Char(']')) && ke.modifiers == Modifiers::ALT;
+            if is_start && self.clipboard.is_expecting_reply() {
+                self.osc = Some(OscReply {
+                    keys: vec![ke],
+                    body: String::new(),
+                    last_key: now,
+                });
+                return true;
+            }
+            return false;
+        };
+
+        let is_end = match ke.code {
+            KeyCode::Char('\\') => ke.modifiers == Modifiers::ALT,
+            KeyCode::Char('g') => ke.modifiers == Modifiers::CONTROL,
+            _ => false,
+        };
+        if is_end {
+            if let Some((selection, contents)) = clipboard::parse_reply(&reply.body) {
+                self.osc = None;
+                self.clipboard.reply_received();
+                self.queue.push_back(Event::Clipboard {
+                    selection,
+                    contents,
+                });
+                return true;
+            }
+        } else if let KeyCode::Char(ch) = ke.code
+            && !ke.modifiers.intersects(Modifiers::ALT | Modifiers::CONTROL)
+        {
+            reply.body.push(ch);
+            reply.keys.push(ke);
+            reply.last_key = now;
+            if clipboard::is_reply_prefix(&reply.body) {
+                return true;
+            }
+            // this key is replayed along with the rest
+            self.replay_osc();
+            return true;
+        }
+
+        self.replay_osc();
+        false
+    }
+
+    /// When the OSC reply being collected is given up on
+    fn osc_deadline(&self) -> Option<Instant> {
+        self.osc
+            .as_ref()
+            .map(|reply| reply.last_key + OscReply::TIMEOUT)
+    }
+
+    fn expire_osc(&mut self, now: Instant) {
+        if self.osc_deadline().is_some_and(|deadline| now >= deadline) {
+            self.replay_osc();
+        }
+    }
+
+    /// Passes on the keys of something that wasn't an OSC reply after all
+    fn replay_osc(&mut self) {
+        for ke in self.osc.take().into_iter().flat_map(|reply| reply.keys) {
+            self.push_key(ke);
+        }
+    }
+
+    fn push_key(&mut self, ke: termina::event::KeyEvent) {
+        use termina::event::KeyEventKind as T;
+
+        let Some(mut key) = Key::from_termina(ke.code) else {
             return;
         };
         let mut modifiers = KeyModifiers::from_termina(ke.modifiers);
-        if let Key::Char(ch) = key {
-            if ch.is_uppercase() || ascii_is_uppercase_symbols(ch) {
-                modifiers |= KeyModifiers::SHIFT
+        // without the alternate keys only the base key is known, letters are the same on any layout
+        if let Key::Char(ch) = &mut key
+            && modifiers.contains(KeyModifiers::SHIFT)
+        {
+            *ch = ch.to_ascii_uppercase();
+        }
+        if let Key::Char(ch) = key
+            && (ch.is_uppercase() || ascii_is_uppercase_symbols(ch))
+        {
+            modifiers |= KeyModifiers::SHIFT
+        }
+
+        let ev = match ke.kind {
+            T::Press => Event::KeyPress { key, modifiers },
+            T::Repeat => Event::KeyRepeat { key, modifiers },
+            T::Release => Event::KeyRelease { key, modifiers },
+        };
+        self.queue.push_back(ev);
+    }
+
+    fn within_tolerance(&self, left: Position, right: Position) -> bool {
+        let Delta { x, y } = left.delta(right);
+        x.unsigned_abs().max(y.unsigned_abs()) <= self.click_tolerance
+    }
+
+    fn click(
+        &mut self,
+        button: MouseButton,
+        modifiers: KeyModifiers,
+        pos: Position,
+        pixel: Option<PixelPosition>,
+        now: Instant,
+    ) -> Option<Event> {
+        let (pressed, origin) = self.pressed.take()?;
+        if pressed != button || !self.within_tolerance(origin, pos) {
+            return None;
+        }
+
+        let count = match self.last_click {
+            Some(last)
+                if last.button == button
+                    && now.saturating_duration_since(last.at) <= self.click_interval
+                    && self.within_tolerance(last.pos, pos) =>
+            {
+                last.count.saturating_add(1)
+            }
+            _ => 1,
+        };
+
+        self.last_click = Some(Click {
+            button,
+            pos,
+            at: now,
+            count,
+        });
+
+        Some(Event::MouseClick {
+            button,
+            modifiers,
+            count,
+            pos,
+            pixel,
+        })
+    }
+
+    fn update_hover(&mut self, pos: Position) {
+        let mut hovered = Vec::with_capacity(self.hovered.len());
+        self.hover_regions.hit_test(pos, |id| hovered.push(id));
+
+        for &region in self.hovered.iter().filter(|id| !hovered.contains(id)) {
+            self.queue.push_back(Event::MouseLeave { region, pos });
+        }
+        for &region in hovered.iter().filter(|id| !self.hovered.contains(id)) {
+            self.queue.push_back(Event::MouseEnter { region, pos });
+        }
+
+        self.hovered = hovered;
+    }
+
+    fn mouse_position(&self, column: u16, row: u16) -> (Position, Option<PixelPosition>) {
+        let pos = Position::new(column as _, row as _);
+        // positions are relative to the viewport, so they're negative above an inline one
+        let origin = self.viewport.origin();
+        match self.cell_size {
+            Some(cell_size) if self.pixel_mouse => {
+                let pixel = PixelPosition {
+                    pixel: pos - Position::new(0, origin.y * cell_size.height as i32),
+                    cell_size,
+                };
+                (pixel.cell(), Some(pixel))
             }
+            _ => (pos - origin, None),
         }
-        self.queue.push_back(Event::KeyPress { key, modifiers });
     }
 
-    fn translate_mouse(&mut self, me: termina::event::MouseEvent) {
+    #[allow(clippy::collapsible_if)]
+    fn translate_mouse(&mut self, me: termina::event::MouseEvent, now: Instant) {
         use termina::event::MouseEventKind as T;
         let modifiers = KeyModifiers::from_termina(me.modifiers);
-        let pos = Position::new(me.column as _, me.row as _);
+        let (pos, pixel) = self.mouse_position(me.column, me.row);
         self.pos = pos;
+        self.update_hover(pos);
+
+        let mut click = None;
 
         let ev = match me.kind {
             T::Down(button) => {
@@ -338,16 +1293,22 @@ impl EventState {
                     *state = DragState::Maybe { origin: pos };
                 };
 
+                let button = MouseButton::from_termina(button);
+                self.pressed = Some((button, pos));
+
                 Event::MousePress {
-                    button: MouseButton::from_termina(button),
+                    button,
                     modifiers,
                     pos,
                     down: true,
+                    pixel,
                 }
             }
 
             T::Up(button) => {
                 let button = MouseButton::from_termina(button);
+                click = self.click(button, modifiers, pos, pixel, now);
+
                 if let DragState::Active {
                     origin,
                     button: old,
@@ -361,11 +1322,15 @@ impl EventState {
                             DragState::Maybe { origin: pos },
                         );
 
+                        // a drag isn't a click
+                        click = None;
+
                         self.queue.push_back(Event::MouseDragRelease {
                             button,
                             modifiers,
                             origin,
                             pos,
+                            pixel,
                         });
                     }
                 }
@@ -375,6 +1340,7 @@ impl EventState {
                     modifiers,
                     pos,
                     down: false,
+                    pixel,
                 }
             }
 
@@ -387,7 +1353,8 @@ impl EventState {
                         button,
                     } => {
                         let previous = std::mem::replace(previous, pos);
-                        if previous == pos {
+                        // with pixel reporting, movement within a cell is still interesting
+                        if previous == pos && pixel.is_none() {
                             return;
                         }
                         Event::MouseDragHeld {
@@ -396,6 +1363,7 @@ impl EventState {
                             origin,
                             pos,
                             delta: previous.delta(pos),
+                            pixel,
                         }
                     }
 
@@ -411,6 +1379,7 @@ impl EventState {
                             origin,
                             pos,
                             delta: origin.delta(pos),
+                            pixel,
                         }
                     }
 
@@ -420,6 +1389,7 @@ impl EventState {
                         origin: pos,
                         pos,
                         delta: <Delta<i32>>::ZERO,
+                        pixel,
                     },
                 }
             }
@@ -430,7 +1400,11 @@ impl EventState {
                         return;
                     }
                 }
-                Event::MouseMove { pos, modifiers }
+                Event::MouseMove {
+                    pos,
+                    modifiers,
+                    pixel,
+                }
             }
 
             T::ScrollDown => Event::MouseScroll {
@@ -451,6 +1425,66 @@ impl EventState {
         };
 
         self.queue.push_back(ev);
+        self.queue.extend(click);
+    }
+}
+
+fn cell_size(window_size: &termina::WindowSize) -> Option<Size> {
+    let (cols, rows) = (window_size.cols, window_size.rows);
+    if cols == 0 || rows == 0 {
+        return None;
+    }
+    let width = window_size.pixel_width? / cols;
+    let height = window_size.pixel_height? / rows;
+    if width == 0 || height == 0 {
+        return None;
+    }
+    Some(Size::new(width as _, height as _))
+}
+
+/// A mouse position in pixels, reported with [`Config::pixel_mouse`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PixelPosition {
+    /// The position in pixels from the top-left of the terminal
+    pub pixel: Position,
+    /// The size of a cell in pixels
+    pub cell_size: Size,
+}
+
+impl PixelPosition {
+    /// The cell containing this position
+    pub const fn cell(&self) -> Position {
+        let (w, h) = self.cell_extent();
+        Position::new(self.pixel.x / w, self.pixel.y / h)
+    }
+
+    /// The position in pixels within the cell
+    pub const fn offset(&self) -> Position {
+        let (w, h) = self.cell_extent();
+        Position::new(self.pixel.x % w, self.pixel.y % h)
+    }
+
+    /// The position in fractional cells, e.g. `1.5` is halfway through the second cell
+    pub const fn sub_cell(&self) -> Position<f32> {
+        let (w, h) = self.cell_extent();
+        Position::new(
+            self.pixel.x as f32 / w as f32,
+            self.pixel.y as f32 / h as f32,
+        )
+    }
+
+    const fn cell_extent(&self) -> (i32, i32) {
+        let w = if self.cell_size.width == 0 {
+            1
+        } else {
+            self.cell_size.width as i32
+        };
+        let h = if self.cell_size.height == 0 {
+            1
+        } else {
+            self.cell_size.height as i32
+        };
+        (w, h)
     }
 }
 
@@ -481,7 +1515,15 @@ impl KeyModifiers {
     pub const CONTROL: Self = Self(1 << 3);
     pub const SUPER: Self = Self(1 << 4);
     pub const HYPER: Self = Self(1 << 5);
-    pub const META: Self = Self(1 << 5);
+    pub const META: Self = Self(1 << 6);
+
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
 }
 
 impl std::ops::BitAnd for KeyModifiers {
@@ -519,18 +1561,20 @@ impl std::ops::Not for KeyModifiers {
 
 impl KeyModifiers {
     fn from_termina(modifiers: termina::event::Modifiers) -> Self {
-        const THEIRS: [termina::event::Modifiers; 6] = [
-            termina::event::Modifiers::SHIFT,
-            termina::event::Modifiers::ALT,
-            termina::event::Modifiers::CONTROL,
-            termina::event::Modifiers::SUPER,
-            termina::event::Modifiers::HYPER,
-            termina::event::Modifiers::META,
+        use termina::event::Modifiers as T;
+        const MAPPING: [(T, KeyModifiers); 6] = [
+            (T::SHIFT, KeyModifiers::SHIFT),
+            (T::ALT, KeyModifiers::ALT),
+            (T::CONTROL, KeyModifiers::CONTROL),
+            (T::SUPER, KeyModifiers::SUPER),
+            (T::HYPER, KeyModifiers::HYPER),
+            (T::META, KeyModifiers::META),
         ];
 
-        THEIRS
+        MAPPING
             .into_iter()
-            .fold(Self::NONE, |this, m| this | Self((modifiers & m).bits()))
+            .filter(|(theirs, _)| modifiers.contains(*theirs))
+            .fold(Self::NONE, |this, (_, ours)| this | ours)
     }
 }
 
@@ -643,9 +1687,79 @@ pub enum Key {
     Pause,
     Menu,
     Function(u8),
+    /// A modifier key by itself, this is only reported with [`Config::kitty_keyboard`]
+    Modifier(ModifierKey),
     Null,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ModifierKey {
+    LeftShift,
+    LeftControl,
+    LeftAlt,
+    LeftSuper,
+    LeftHyper,
+    LeftMeta,
+    RightShift,
+    RightControl,
+    RightAlt,
+    RightSuper,
+    RightHyper,
+    RightMeta,
+}
+
+impl ModifierKey {
+    pub const fn is_left(&self) -> bool {
+        matches!(
+            self,
+            Self::LeftShift
+                | Self::LeftControl
+                | Self::LeftAlt
+                | Self::LeftSuper
+                | Self::LeftHyper
+                | Self::LeftMeta
+        )
+    }
+
+    pub const fn is_right(&self) -> bool {
+        !self.is_left()
+    }
+
+    /// The [`KeyModifiers`] flag this key toggles, without its side
+    pub const fn modifier(&self) -> KeyModifiers {
+        match self {
+            Self::LeftShift | Self::RightShift => KeyModifiers::SHIFT,
+            Self::LeftControl | Self::RightControl => KeyModifiers::CONTROL,
+            Self::LeftAlt | Self::RightAlt => KeyModifiers::ALT,
+            Self::LeftSuper | Self::RightSuper => KeyModifiers::SUPER,
+            Self::LeftHyper | Self::RightHyper => KeyModifiers::HYPER,
+            Self::LeftMeta | Self::RightMeta => KeyModifiers::META,
+        }
+    }
+
+    fn from_termina(key: termina::event::ModifierKeyCode) -> Option<Self> {
+        use termina::event::ModifierKeyCode as T;
+        let this = match key {
+            T::LeftShift => Self::LeftShift,
+            T::LeftControl => Self::LeftControl,
+            T::LeftAlt => Self::LeftAlt,
+            T::LeftSuper => Self::LeftSuper,
+            T::LeftHyper => Self::LeftHyper,
+            T::LeftMeta => Self::LeftMeta,
+            T::RightShift => Self::RightShift,
+            T::RightControl => Self::RightControl,
+            T::RightAlt => Self::RightAlt,
+            T::RightSuper => Self::RightSuper,
+            T::RightHyper => Self::RightHyper,
+            T::RightMeta => Self::RightMeta,
+            #[allow(unreachable_patterns)]
+            _ => return None,
+        };
+        Some(this)
+    }
+}
+
 impl Key {
     fn from_termina(key: termina::event::KeyCode) -> Option<Self> {
         use termina::event::KeyCode as T;
@@ -675,8 +1789,175 @@ impl Key {
             T::Menu => Self::Menu,
             T::Null => Self::Null,
             T::Function(f) => Self::Function(f),
+            T::Modifier(key) => Self::Modifier(ModifierKey::from_termina(key)?),
             _ => return None,
         };
         Some(this)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use termina::event::{KeyCode, KeyEvent, Modifiers};
+
+    fn event_state() -> EventState {
+        EventState::new(
+            Config::new(),
+            None,
+            Size::new(80, 24),
+            HoverRegions::default(),
+            Clipboard::default(),
+            Viewport::fullscreen(),
+        )
+    }
+
+    fn press(state: &mut EventState, code: KeyCode, modifiers: Modifiers) -> Keybind {
+        let events = state
+            .translate(
+                &termina::Event::Key(KeyEvent::new(code, modifiers)),
+                Instant::now(),
+            )
+            .collect::<Vec<_>>();
+        match events.as_slice() {
+            &[Event::KeyPress { key, modifiers }] => Keybind { key, modifiers },
+            events => panic!("expected a single key press, got {events:?}"),
+        }
+    }
+
+    #[test]
+    fn shifted_keys() {
+        let mut state = event_state();
+
+        // legacy encoding and kitty's alternate keys report the shifted key without shift
+        for ch in ['A', '!', '?'] {
+            let keybind = press(&mut state, KeyCode::Char(ch), Modifiers::NONE);
+            assert_eq!(keybind, Keybind::char(ch), "{ch:?}");
+        }
+
+        // kitty without the alternate keys reports the base key with shift
+        let keybind = press(&mut state, KeyCode::Char('a'), Modifiers::SHIFT);
+        assert_eq!(keybind, Keybind::char('A'));
+
+        let keybind = press(
+            &mut state,
+            KeyCode::Char('a'),
+            Modifiers::SHIFT | Modifiers::CONTROL,
+        );
+        assert_eq!(keybind, Keybind::char('A').control());
+
+        let keybind = press(&mut state, KeyCode::Char('a'), Modifiers::NONE);
+        assert_eq!(keybind, Keybind::char('a'));
+    }
+
+    fn key(ch: char, modifiers: Modifiers) -> termina::Event {
+        termina::Event::Key(KeyEvent::new(KeyCode::Char(ch), modifiers))
+    }
+
+    /// The keys a terminal sends an OSC reply as, ending it with `ESC \`
+    fn osc_keys(body: &str) -> Vec<termina::Event> {
+        let mut keys = vec![key(']', Modifiers::ALT)];
+        keys.extend(body.chars().map(|ch| key(ch, Modifiers::NONE)));
+        keys.push(key('\\', Modifiers::ALT));
+        keys
+    }
+
+    fn feed(state: &mut EventState, events: &[termina::Event], now: Instant) -> Vec<Event> {
+        events
+            .iter()
+            .flat_map(|ev| state.translate(ev, now).collect::<Vec<_>>())
+            .collect()
+    }
+
+    fn key_press(ch: char, modifiers: KeyModifiers) -> Event {
+        Event::KeyPress {
+            key: Key::Char(ch),
+            modifiers,
+        }
+    }
+
+    #[test]
+    fn osc_reply() {
+        let mut state = event_state();
+        let now = Instant::now();
+
+        // without a request, the same keys are just keys
+        let events = feed(&mut state, &osc_keys("52;c;aGk="), now);
+        assert_eq!(events.len(), 11);
+        assert_eq!(events[0], key_press(']', KeyModifiers::ALT));
+
+        state.clipboard.expect_reply();
+        let events = feed(&mut state, &osc_keys("52;c;aGVsbG8="), now);
+        assert_eq!(
+            events,
+            [Event::Clipboard {
+                selection: ClipboardSelection::Clipboard,
+                contents: "hello".into(),
+            }]
+        );
+        assert!(!state.clipboard.is_expecting_reply());
+    }
+
+    #[test]
+    fn osc_reply_mismatch() {
+        let mut state = event_state();
+        let now = Instant::now();
+        state.clipboard.expect_reply();
+
+        // alt+] followed by something that isn't a reply is passed on as it was
+        let events = feed(
+            &mut state,
+            &[
+                key(']', Modifiers::ALT),
+                key('5', Modifiers::NONE),
+                key('x', Modifiers::NONE),
+            ],
+            now,
+        );
+        assert_eq!(
+            events,
+            [
+                key_press(']', KeyModifiers::ALT),
+                key_press('5', KeyModifiers::NONE),
+                key_press('x', KeyModifiers::NONE),
+            ]
+        );
+        assert!(state.clipboard.is_expecting_reply());
+    }
+
+    #[test]
+    fn osc_reply_timeout() {
+        let mut state = event_state();
+        let now = Instant::now();
+        state.clipboard.expect_reply();
+
+        let start = [key(']', Modifiers::ALT), key('5', Modifiers::NONE)];
+        assert_eq!(feed(&mut state, &start, now), []);
+        assert_eq!(state.osc_deadline(), Some(now + OscReply::TIMEOUT));
+        assert_eq!(state.tick(now + OscReply::TIMEOUT / 2).count(), 0);
+
+        // the keys are let go once nothing more arrives in time
+        let events = state.tick(now + OscReply::TIMEOUT).collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            [
+                key_press(']', KeyModifiers::ALT),
+                key_press('5', KeyModifiers::NONE),
+            ]
+        );
+        assert_eq!(state.osc_deadline(), None);
+
+        // a key that arrives too late lets them go before it's handled
+        assert_eq!(feed(&mut state, &start, now), []);
+        let later = now + OscReply::TIMEOUT * 2;
+        let events = feed(&mut state, &[key('2', Modifiers::NONE)], later);
+        assert_eq!(
+            events,
+            [
+                key_press(']', KeyModifiers::ALT),
+                key_press('5', KeyModifiers::NONE),
+                key_press('2', KeyModifiers::NONE),
+            ]
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::{HashMap, VecDeque};
+
+use mars_math::Size;
+
+use crate::{Backend, Clipboard, ClipboardSelection, Event, HoverRegions};
+
+/// A [`Backend`] without a tty, it plays back scripted events and captures everything written to it
+///
+/// Once the script has run out one more frame is let through, then [`Event::Quit`] is returned
+/// so a run loop comes to an end
+#[derive(Debug)]
+pub struct MockTerminal {
+    size: Size,
+    // `None` ends the current frame
+    events: VecDeque<Option<Event>>,
+    output: Vec<u8>,
+    quit_when_empty: bool,
+    finished: bool,
+    closed: bool,
+    clipboard: Clipboard,
+    hover_regions: HoverRegions,
+    selections: HashMap<ClipboardSelection, String>,
+}
+
+impl MockTerminal {
+    pub fn new(size: Size) -> Self {
+        Self {
+            size,
+            events: VecDeque::new(),
+            output: Vec::new(),
+            quit_when_empty: true,
+            finished: false,
+            closed: false,
+            clipboard: Clipboard::default(),
+            hover_regions: HoverRegions::default(),
+            selections: HashMap::new(),
+        }
+    }
+
+    pub fn events(mut self, events: impl IntoIterator<Item = Event>) -> Self {
+        self.events.extend(events.into_iter().map(Some));
+        self
+    }
+
+    /// Whether to quit once the scripted events have run out, this is on by default
+    pub const fn quit_when_empty(mut self, quit_when_empty: bool) -> Self {
+        self.quit_when_empty = quit_when_empty;
+        self
+    }
+
+    pub fn push_event(&mut self, event: Event) {
+        self.events.push_back(Some(event));
+    }
+
+    /// Ends the current frame, events pushed after this are delivered in the next one
+    pub fn push_frame(&mut self) {
+        self.events.push_back(None);
+    }
+
+    /// Makes every write fail with [`BrokenPipe`](std::io::ErrorKind::BrokenPipe) from now on, as if the tty went away
+    pub const fn close(&mut self) {
+        self.closed = true;
+    }
+
+    /// Everything written so far
+    pub fn output(&self) -> &[u8] {
+        &self.output
+    }
+
+    /// Takes everything written so far, leaving the output empty
+    pub fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.output)
+    }
+
+    /// What was last copied to the selection
+    pub fn selection(&self, selection: ClipboardSelection) -> Option<&str> {
+        self.selections.get(&selection).map(String::as_str)
+    }
+
+    /// Sets the selection's contents, as if it was copied from another program
+    pub fn set_selection(&mut self, selection: ClipboardSelection, contents: impl Into<String>) {
+        self.selections.insert(selection, contents.into());
+    }
+}
+
+impl std::io::Write for MockTerminal {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.closed {
+            return Err(std::io::ErrorKind::BrokenPipe.into());
+        }
+        self.output.write(buf)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> std::io::Result<()> {
+        if self.closed {
+            return Err(std::io::ErrorKind::BrokenPipe.into());
+        }
+        Ok(())
+    }
+}
+
+impl Backend for MockTerminal {
+    fn size(&self) -> Size {
+        self.size
+    }
+
+    fn try_read_event(&mut self) -> Option<Event> {
+        let Some(step) = self.events.pop_front() else {
+            // let the last events be drawn before quitting
+            if self.quit_when_empty && std::mem::replace(&mut self.finished, true) {
+                return Some(Event::Quit);
+            }
+            return None;
+        };
+
+        if let Some(Event::Resize { size }) = step {
+            self.size = size;
+        }
+        step
+    }
+
+    fn clipboard(&self) -> Clipboard {
+        self.clipboard.clone()
+    }
+
+    fn hover_regions(&self) -> HoverRegions {
+        self.hover_regions.clone()
+    }
+
+    fn flush_clipboard(&mut self) -> std::io::Result<()> {
+        use crate::clipboard::ClipboardRequest as R;
+        for request in self.clipboard.drain() {
+            match request {
+                R::Copy(selection, text) => {
+                    crate::clipboard::check_len(&text)?;
+                    self.selections.insert(selection, text);
+                }
+                R::Read(selection) => {
+                    let contents = self.selections.get(&selection).cloned();
+                    if let Some(contents) = contents {
+                        let ev = Event::Clipboard {
+                            selection,
+                            contents,
+                        };
+                        self.events.push_front(Some(ev));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,876 @@
+use std::{
+    collections::VecDeque,
+    fmt::Write as _,
+    time::{Duration, Instant},
+};
+
+use mars_math::{Delta, Position, Size};
+
+use crate::{
+    Backend, Clipboard, ClipboardSelection, Event, HoverRegions, Keybind, MouseButton,
+    PixelPosition, RegionId,
+    keybind::{ModifierNames, parse_modifiers},
+};
+
+/// Wraps a [`Backend`], writing every event read from it to `out`
+pub struct Recorder<B, W> {
+    inner: B,
+    out: W,
+    start: Instant,
+}
+
+impl<B: Backend, W: std::io::Write> Recorder<B, W> {
+    /// Starts recording, the first line is the backend's current size
+    pub fn new(inner: B, mut out: W) -> std::io::Result<Self> {
+        let size = inner.size();
+        writeln!(&mut out, "# mars recording")?;
+        writeln!(
+            &mut out,
+            "{}",
+            encode(Duration::ZERO, &Event::Resize { size })
+        )?;
+        out.flush()?;
+
+        Ok(Self {
+            inner,
+            out,
+            start: Instant::now(),
+        })
+    }
+
+    pub fn into_inner(self) -> (B, W) {
+        (self.inner, self.out)
+    }
+}
+
+impl<B: Backend, W: std::io::Write> std::io::Write for Recorder<B, W> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<B: Backend, W: std::io::Write> Backend for Recorder<B, W> {
+    fn size(&self) -> Size {
+        self.inner.size()
+    }
+
+    fn screen_size(&self) -> Size {
+        self.inner.screen_size()
+    }
+
+    fn origin(&self) -> Position {
+        self.inner.origin()
+    }
+
+    fn try_read_event(&mut self) -> Option<Event> {
+        let ev = self.inner.try_read_event()?;
+        // a failed write shouldn't take the application down with it, the recording just ends early
+        let line = encode(self.start.elapsed(), &ev);
+        _ = writeln!(&mut self.out, "{line}").and_then(|_| self.out.flush());
+        Some(ev)
+    }
+
+    fn clipboard(&self) -> Clipboard {
+        self.inner.clipboard()
+    }
+
+    fn hover_regions(&self) -> HoverRegions {
+        self.inner.hover_regions()
+    }
+
+    fn flush_clipboard(&mut self) -> std::io::Result<()> {
+        self.inner.flush_clipboard()
+    }
+
+    fn suspend(&mut self) -> std::io::Result<()> {
+        self.inner.suspend()
+    }
+
+    fn resume(&mut self) -> std::io::Result<()> {
+        self.inner.resume()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseRecordingError {
+    /// The line the error is on, starting at 1
+    pub line: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for ParseRecordingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseRecordingError {}
+
+/// The events of a recorded session
+///
+/// A recording is plain text, one event per line. Each line starts with the milliseconds since the recording
+/// started and the event's name, followed by its fields as `name=value`:
+///
+/// ```text
+/// # lines starting with a `#` are ignored
+/// 0 resize size=80x24
+/// 1520 key-press key=ctrl+x
+/// 1733 mouse-press button=primary pos=12,4 down=true mods=ctrl
+/// 1802 mouse-drag-held button=primary origin=12,4 pos=14,4 delta=2,0 pixel=145,41/10x20
+/// 2010 paste text=hello\sworld\n
+/// ```
+///
+/// Positions and deltas are `x,y`, sizes are `WxH` and pixel positions are `x,y/WxH` (the pixel, then the cell size).
+/// Keys are formatted like [`Keybind`]s, modifiers like `ctrl+alt`. Text escapes spaces as `\s`,
+/// along with `\\`, `\n`, `\r`, `\t` and `\u{..}` for other control characters.
+///
+/// The events are `key-press`, `key-repeat`, `key-release`, `mouse-move`, `mouse-scroll`, `mouse-press`,
+/// `mouse-drag-held`, `mouse-drag-release`, `mouse-click`, `mouse-enter`, `mouse-leave`, `resize`, `paste`,
+/// `focus-gained`, `focus-lost`, `clipboard`, `suspend`, `resume` and `quit`, with fields named after those of [`Event`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Recording {
+    events: Vec<(Duration, Event)>,
+}
+
+impl Recording {
+    pub fn parse(input: &str) -> Result<Self, ParseRecordingError> {
+        let events = input
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+            .map(|(index, line)| {
+                decode(line).map_err(|message| ParseRecordingError {
+                    line: index + 1,
+                    message,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { events })
+    }
+
+    /// The events along with when they happened, relative to the start of the recording
+    pub fn events(&self) -> &[(Duration, Event)] {
+        &self.events
+    }
+
+    pub fn push(&mut self, time: Duration, event: Event) {
+        self.events.push((time, event));
+    }
+
+    /// The size of the terminal when the recording started, if it's known
+    pub fn initial_size(&self) -> Option<Size> {
+        match self.events.first() {
+            Some((_, Event::Resize { size })) => Some(*size),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Recording {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "# mars recording")?;
+        for (time, ev) in &self.events {
+            writeln!(f, "{}", encode(*time, ev))?;
+        }
+        Ok(())
+    }
+}
+
+/// A [`Backend`] that plays back a [`Recording`], rendering to another backend
+///
+/// Events from the other backend are ignored, except for [`Event::Quit`] so a replay on a real terminal can be stopped
+/// (a [`MockTerminal`](crate::MockTerminal) should be told not to [`quit_when_empty`](crate::MockTerminal::quit_when_empty)).
+/// Once the recording has run out one more frame is let through, then [`Event::Quit`] is returned
+pub struct Replay<B> {
+    inner: B,
+    events: VecDeque<(Duration, Event)>,
+    speed: f32,
+    start: Option<Instant>,
+    size: Size,
+    finished: bool,
+    clipboard: Clipboard,
+    hover_regions: HoverRegions,
+}
+
+impl<B: Backend> Replay<B> {
+    pub fn new(recording: Recording, inner: B) -> Self {
+        Self {
+            size: recording.initial_size().unwrap_or_else(|| inner.size()),
+            inner,
+            events: recording.events.into(),
+            speed: 1.0,
+            start: None,
+            finished: false,
+            clipboard: Clipboard::default(),
+            hover_regions: HoverRegions::default(),
+        }
+    }
+
+    /// How much faster than the original to play the events back, [`f32::INFINITY`] delivers them all at once
+    pub fn speed(mut self, speed: f32) -> Self {
+        assert!(speed > 0.0, "speed must be positive");
+        self.speed = speed;
+        self
+    }
+
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+
+    /// Whether every event has been delivered
+    pub fn is_finished(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+impl<B: Backend> std::io::Write for Replay<B> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<B: Backend> Backend for Replay<B> {
+    fn size(&self) -> Size {
+        self.size
+    }
+
+    fn origin(&self) -> Position {
+        self.inner.origin()
+    }
+
+    fn try_read_event(&mut self) -> Option<Event> {
+        if let Some(Event::Quit) = self.inner.try_read_event() {
+            return Some(Event::Quit);
+        }
+
+        let start = *self.start.get_or_insert_with(Instant::now);
+        let Some((time, _)) = self.events.front() else {
+            // let the last events be drawn before quitting
+            if std::mem::replace(&mut self.finished, true) {
+                return Some(Event::Quit);
+            }
+            return None;
+        };
+
+        let elapsed = start.elapsed().as_secs_f64() * self.speed as f64;
+        if time.as_secs_f64() > elapsed {
+            return None;
+        }
+
+        let (_, ev) = self.events.pop_front()?;
+        if let Event::Resize { size } = ev {
+            self.size = size;
+        }
+        Some(ev)
+    }
+
+    fn clipboard(&self) -> Clipboard {
+        self.clipboard.clone()
+    }
+
+    fn hover_regions(&self) -> HoverRegions {
+        self.hover_regions.clone()
+    }
+
+    // the clipboard replies are part of the recording
+    fn flush_clipboard(&mut self) -> std::io::Result<()> {
+        _ = self.clipboard.drain();
+        Ok(())
+    }
+}
+
+fn encode(time: Duration, ev: &Event) -> String {
+    let mut out = format!("{} ", time.as_millis());
+    let line = &mut out;
+
+    let key = |line: &mut String, name, key, modifiers| {
+        _ = write!(line, "{name} key={}", Keybind { key, modifiers });
+    };
+    let mods = |line: &mut String, modifiers: crate::KeyModifiers| {
+        if !modifiers.is_empty() {
+            _ = write!(line, " mods={}", ModifierNames(modifiers));
+        }
+    };
+    let pixel = |line: &mut String, pixel: &Option<PixelPosition>| {
+        if let Some(PixelPosition { pixel, cell_size }) = pixel {
+            _ = write!(
+                line,
+                " pixel={},{}/{}x{}",
+                pixel.x, pixel.y, cell_size.width, cell_size.height
+            );
+        }
+    };
+
+    match ev {
+        &Event::KeyPress { key: k, modifiers } => key(line, "key-press", k, modifiers),
+        &Event::KeyRepeat { key: k, modifiers } => key(line, "key-repeat", k, modifiers),
+        &Event::KeyRelease { key: k, modifiers } => key(line, "key-release", k, modifiers),
+        Event::MouseMove {
+            pos,
+            modifiers,
+            pixel: p,
+        } => {
+            _ = write!(line, "mouse-move pos={},{}", pos.x, pos.y);
+            mods(line, *modifiers);
+            pixel(line, p);
+        }
+        Event::MouseScroll { delta } => {
+            _ = write!(line, "mouse-scroll delta={},{}", delta.x, delta.y);
+        }
+        Event::MousePress {
+            button,
+            modifiers,
+            pos,
+            down,
+            pixel: p,
+        } => {
+            _ = write!(
+                line,
+                "mouse-press button={} pos={},{} down={down}",
+                button_name(*button),
+                pos.x,
+                pos.y
+            );
+            mods(line, *modifiers);
+            pixel(line, p);
+        }
+        Event::MouseDragHeld {
+            button,
+            modifiers,
+            origin,
+            pos,
+            delta,
+            pixel: p,
+        } => {
+            _ = write!(
+                line,
+                "mouse-drag-held button={} origin={},{} pos={},{} delta={},{}",
+                button_name(*button),
+                origin.x,
+                origin.y,
+                pos.x,
+                pos.y,
+                delta.x,
+                delta.y
+            );
+            mods(line, *modifiers);
+            pixel(line, p);
+        }
+        Event::MouseDragRelease {
+            button,
+            modifiers,
+            origin,
+            pos,
+            pixel: p,
+        } => {
+            _ = write!(
+                line,
+                "mouse-drag-release button={} origin={},{} pos={},{}",
+                button_name(*button),
+                origin.x,
+                origin.y,
+                pos.x,
+                pos.y
+            );
+            mods(line, *modifiers);
+            pixel(line, p);
+        }
+        Event::MouseClick {
+            button,
+            modifiers,
+            count,
+            pos,
+            pixel: p,
+        } => {
+            _ = write!(
+                line,
+                "mouse-click button={} count={count} pos={},{}",
+                button_name(*button),
+                pos.x,
+                pos.y
+            );
+            mods(line, *modifiers);
+            pixel(line, p);
+        }
+        Event::MouseEnter { region, pos } => {
+            _ = write!(
+                line,
+                "mouse-enter region={} pos={},{}",
+                region.0, pos.x, pos.y
+            );
+        }
+        Event::MouseLeave { region, pos } => {
+            _ = write!(
+                line,
+                "mouse-leave region={} pos={},{}",
+                region.0, pos.x, pos.y
+            );
+        }
+        Event::Resize { size } => {
+            _ = write!(line, "resize size={}x{}", size.width, size.height);
+        }
+        Event::Paste(text) => {
+            _ = write!(line, "paste text={}", escape(text));
+        }
+        Event::FocusGained => line.push_str("focus-gained"),
+        Event::FocusLost => line.push_str("focus-lost"),
+        Event::Clipboard {
+            selection,
+            contents,
+        } => {
+            let selection = match selection {
+                ClipboardSelection::Clipboard => "clipboard",
+                ClipboardSelection::Primary => "primary",
+            };
+            _ = write!(
+                line,
+                "clipboard selection={selection} text={}",
+                escape(contents)
+            );
+        }
+        Event::Suspend => line.push_str("suspend"),
+        Event::Resume => line.push_str("resume"),
+        Event::Quit => line.push_str("quit"),
+    }
+    out
+}
+
+fn decode(line: &str) -> Result<(Duration, Event), String> {
+    let mut parts = line.split_whitespace();
+    let time = parts.next().ok_or("missing time")?;
+    let time = time
+        .parse()
+        .map(Duration::from_millis)
+        .map_err(|_| format!("invalid time `{time}`"))?;
+    let name = parts.next().ok_or("missing event")?;
+
+    let fields = Fields(
+        parts
+            .map(|field| {
+                field
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected `name=value`, found `{field}`"))
+            })
+            .collect::<Result<_, _>>()?,
+    );
+
+    let ev = match name {
+        "key-press" | "key-repeat" | "key-release" => {
+            let Keybind { key, modifiers } = fields.parse("key", |s| s.parse().ok())?;
+            match name {
+                "key-press" => Event::KeyPress { key, modifiers },
+                "key-repeat" => Event::KeyRepeat { key, modifiers },
+                _ => Event::KeyRelease { key, modifiers },
+            }
+        }
+        "mouse-move" => Event::MouseMove {
+            pos: fields.parse("pos", parse_position)?,
+            modifiers: fields.modifiers()?,
+            pixel: fields.pixel()?,
+        },
+        "mouse-scroll" => Event::MouseScroll {
+            delta: fields.parse("delta", parse_delta)?,
+        },
+        "mouse-press" => Event::MousePress {
+            button: fields.parse("button", parse_button)?,
+            modifiers: fields.modifiers()?,
+            pos: fields.parse("pos", parse_position)?,
+            down: fields.parse("down", |s| s.parse().ok())?,
+            pixel: fields.pixel()?,
+        },
+        "mouse-drag-held" => Event::MouseDragHeld {
+            button: fields.parse("button", parse_button)?,
+            modifiers: fields.modifiers()?,
+            origin: fields.parse("origin", parse_position)?,
+            pos: fields.parse("pos", parse_position)?,
+            delta: fields.parse("delta", parse_delta)?,
+            pixel: fields.pixel()?,
+        },
+        "mouse-drag-release" => Event::MouseDragRelease {
+            button: fields.parse("button", parse_button)?,
+            modifiers: fields.modifiers()?,
+            origin: fields.parse("origin", parse_position)?,
+            pos: fields.parse("pos", parse_position)?,
+            pixel: fields.pixel()?,
+        },
+        "mouse-click" => Event::MouseClick {
+            button: fields.parse("button", parse_button)?,
+            modifiers: fields.modifiers()?,
+            count: fields.parse("count", |s| s.parse().ok())?,
+            pos: fields.parse("pos", parse_position)?,
+            pixel: fields.pixel()?,
+        },
+        "mouse-enter" => Event::MouseEnter {
+            region: fields.parse("region", |s| s.parse().ok().map(RegionId))?,
+            pos: fields.parse("pos", parse_position)?,
+        },
+        "mouse-leave" => Event::MouseLeave {
+            region: fields.parse("region", |s| s.parse().ok().map(RegionId))?,
+            pos: fields.parse("pos", parse_position)?,
+        },
+        "resize" => Event::Resize {
+            size: fields.parse("size", parse_size)?,
+        },
+        "paste" => Event::Paste(fields.parse("text", unescape)?),
+        "focus-gained" => Event::FocusGained,
+        "focus-lost" => Event::FocusLost,
+        "clipboard" => Event::Clipboard {
+            selection: fields.parse("selection", |s| match s {
+                "clipboard" => Some(ClipboardSelection::Clipboard),
+                "primary" => Some(ClipboardSelection::Primary),
+                _ => None,
+            })?,
+            contents: fields.parse("text", unescape)?,
+        },
+        "suspend" => Event::Suspend,
+        "resume" => Event::Resume,
+        "quit" => Event::Quit,
+        name => return Err(format!("unknown event `{name}`")),
+    };
+    Ok((time, ev))
+}
+
+struct Fields<'a>(Vec<(&'a str, &'a str)>);
+
+impl Fields<'_> {
+    fn get(&self, name: &str) -> Option<&str> {
+        self.0.iter().find(|(k, _)| *k == name).map(|(_, v)| *v)
+    }
+
+    fn parse<T>(&self, name: &str, parse: impl FnOnce(&str) -> Option<T>) -> Result<T, String> {
+        let value = self
+            .get(name)
+            .ok_or_else(|| format!("missing field `{name}`"))?;
+        parse(value).ok_or_else(|| format!("invalid {name} `{value}`"))
+    }
+
+    fn modifiers(&self) -> Result<crate::KeyModifiers, String> {
+        match self.get("mods") {
+            Some(_) => self.parse("mods", parse_modifiers),
+            None => Ok(crate::KeyModifiers::NONE),
+        }
+    }
+
+    fn pixel(&self) -> Result<Option<PixelPosition>, String> {
+        match self.get("pixel") {
+            Some(_) => self.parse("pixel", parse_pixel).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+const fn button_name(button: MouseButton) -> &'static str {
+    match button {
+        MouseButton::Primary => "primary",
+        MouseButton::Secondary => "secondary",
+        MouseButton::Middle => "middle",
+    }
+}
+
+fn parse_button(input: &str) -> Option<MouseButton> {
+    match input {
+        "primary" => Some(MouseButton::Primary),
+        "secondary" => Some(MouseButton::Secondary),
+        "middle" => Some(MouseButton::Middle),
+        _ => None,
+    }
+}
+
+fn parse_pair<T: std::str::FromStr>(input: &str, separator: char) -> Option<(T, T)> {
+    let (x, y) = input.split_once(separator)?;
+    Some((x.parse().ok()?, y.parse().ok()?))
+}
+
+fn parse_position(input: &str) -> Option<Position> {
+    parse_pair(input, ',').map(|(x, y)| Position::new(x, y))
+}
+
+fn parse_delta(input: &str) -> Option<Delta<i32>> {
+    parse_pair(input, ',').map(|(x, y)| Delta::new(x, y))
+}
+
+fn parse_size(input: &str) -> Option<Size> {
+    parse_pair(input, 'x').map(|(w, h)| Size::new(w, h))
+}
+
+fn parse_pixel(input: &str) -> Option<PixelPosition> {
+    let (pixel, cell_size) = input.split_once('/')?;
+    Some(PixelPosition {
+        pixel: parse_position(pixel)?,
+        cell_size: parse_size(cell_size)?,
+    })
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            ' ' => out.push_str("\\s"),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            ch if ch.is_control() || ch.is_whitespace() => {
+                _ = write!(&mut out, "\\u{{{:x}}}", ch as u32);
+            }
+            ch => out.push(ch),
+        }
+    }
+    out
+}
+
+fn unescape(text: &str) -> Option<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            out.push(ch);
+            continue;
+        }
+
+        let ch = match chars.next()? {
+            's' => ' ',
+            '\\' => '\\',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'u' => {
+                let rest = chars.as_str().strip_prefix('{')?;
+                let (hex, tail) = rest.split_once('}')?;
+                chars = tail.chars();
+                char::from_u32(u32::from_str_radix(hex, 16).ok()?)?
+            }
+            _ => return None,
+        };
+        out.push(ch);
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Key, KeyModifiers, MockTerminal, ModifierKey};
+
+    fn all_events() -> Vec<Event> {
+        let pixel = Some(PixelPosition {
+            pixel: Position::new(145, -41),
+            cell_size: Size::new(10, 20),
+        });
+        let mods = KeyModifiers::CONTROL | KeyModifiers::ALT;
+
+        vec![
+            Event::KeyPress {
+                key: Key::Char('a'),
+                modifiers: KeyModifiers::NONE,
+            },
+            Event::KeyPress {
+                key: Key::Char(' '),
+                modifiers: KeyModifiers::CONTROL,
+            },
+            Event::KeyPress {
+                key: Key::Char('='),
+                modifiers: KeyModifiers::NONE,
+            },
+            Event::KeyRepeat {
+                key: Key::Function(5),
+                modifiers: KeyModifiers::SHIFT,
+            },
+            Event::KeyRelease {
+                key: Key::Modifier(ModifierKey::LeftShift),
+                modifiers: KeyModifiers::SUPER | KeyModifiers::HYPER | KeyModifiers::META,
+            },
+            Event::MouseMove {
+                pos: Position::new(3, -2),
+                modifiers: KeyModifiers::NONE,
+                pixel: None,
+            },
+            Event::MouseMove {
+                pos: Position::new(14, -2),
+                modifiers: mods,
+                pixel,
+            },
+            Event::MouseScroll {
+                delta: Delta::new(-1, 0),
+            },
+            Event::MousePress {
+                button: MouseButton::Secondary,
+                modifiers: mods,
+                pos: Position::new(12, 4),
+                down: true,
+                pixel,
+            },
+            Event::MousePress {
+                button: MouseButton::Middle,
+                modifiers: KeyModifiers::NONE,
+                pos: Position::new(12, 4),
+                down: false,
+                pixel: None,
+            },
+            Event::MouseDragHeld {
+                button: MouseButton::Primary,
+                modifiers: KeyModifiers::SHIFT,
+                origin: Position::new(12, 4),
+                pos: Position::new(14, 3),
+                delta: Delta::new(2, -1),
+                pixel,
+            },
+            Event::MouseDragRelease {
+                button: MouseButton::Primary,
+                modifiers: KeyModifiers::NONE,
+                origin: Position::new(12, 4),
+                pos: Position::new(14, 3),
+                pixel: None,
+            },
+            Event::MouseClick {
+                button: MouseButton::Primary,
+                modifiers: mods,
+                count: 3,
+                pos: Position::new(0, 0),
+                pixel,
+            },
+            Event::MouseEnter {
+                region: RegionId(7),
+                pos: Position::new(1, 2),
+            },
+            Event::MouseLeave {
+                region: RegionId(u32::MAX as _),
+                pos: Position::new(1, 2),
+            },
+            Event::Resize {
+                size: Size::new(80, 24),
+            },
+            Event::Paste("hello world\n".into()),
+            Event::Paste(String::new()),
+            Event::FocusGained,
+            Event::FocusLost,
+            Event::Clipboard {
+                selection: ClipboardSelection::Clipboard,
+                contents: "a=b".into(),
+            },
+            Event::Clipboard {
+                selection: ClipboardSelection::Primary,
+                contents: "x".into(),
+            },
+            Event::Suspend,
+            Event::Resume,
+            Event::Quit,
+        ]
+    }
+
+    #[test]
+    fn round_trip() {
+        for (i, ev) in all_events().into_iter().enumerate() {
+            let time = Duration::from_millis(i as u64 * 250);
+            let line = encode(time, &ev);
+            assert_eq!(decode(&line), Ok((time, ev)), "{line}");
+        }
+
+        let mut recording = Recording::default();
+        for (i, ev) in all_events().into_iter().enumerate() {
+            recording.push(Duration::from_millis(i as _), ev);
+        }
+        assert_eq!(Recording::parse(&recording.to_string()), Ok(recording));
+    }
+
+    #[test]
+    fn escaping() {
+        let ev = Event::Paste("a b=c\n\r\t\\d\u{7}\u{a0}é".into());
+        let line = encode(Duration::from_millis(5), &ev);
+        assert_eq!(line, r"5 paste text=a\sb=c\n\r\t\\d\u{7}\u{a0}é");
+        assert_eq!(decode(&line), Ok((Duration::from_millis(5), ev)));
+
+        assert_eq!(unescape(r"\s\s"), Some("  ".into()));
+        assert_eq!(unescape("=="), Some("==".into()));
+        assert_eq!(unescape(r"\u{1f600}"), Some("\u{1f600}".into()));
+
+        for invalid in [r"\", r"\q", r"\u", r"\u{", r"\u{zz}", r"\u{d800}"] {
+            assert_eq!(unescape(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn malformed_lines() {
+        let cases = [
+            ("x key-press key=a", "invalid time `x`"),
+            ("-1 quit", "invalid time `-1`"),
+            ("0", "missing event"),
+            ("0 nope", "unknown event `nope`"),
+            ("0 key-press", "missing field `key`"),
+            ("0 key-press key", "expected `name=value`, found `key`"),
+            ("0 key-press key=ctrl+", "invalid key `ctrl+`"),
+            ("0 resize size=80", "invalid size `80`"),
+            ("0 mouse-scroll delta=1,x", "invalid delta `1,x`"),
+            (
+                "0 mouse-press button=left pos=0,0 down=true",
+                "invalid button `left`",
+            ),
+            (
+                "0 mouse-press button=primary pos=0,0 down=yes",
+                "invalid down `yes`",
+            ),
+            (
+                "0 mouse-move pos=0,0 mods=shift+nope",
+                "invalid mods `shift+nope`",
+            ),
+            ("0 mouse-move pos=0,0 pixel=1,2", "invalid pixel `1,2`"),
+            ("0 paste text=\\q", "invalid text `\\q`"),
+            (
+                "0 clipboard selection=secondary text=a",
+                "invalid selection `secondary`",
+            ),
+        ];
+        for (line, message) in cases {
+            assert_eq!(decode(line), Err(message.to_string()), "{line}");
+        }
+
+        let input = "# mars recording\n\n0 resize size=80x24\n  # comment\n10 nope\n";
+        assert_eq!(
+            Recording::parse(input),
+            Err(ParseRecordingError {
+                line: 5,
+                message: "unknown event `nope`".into(),
+            })
+        );
+    }
+
+    #[test]
+    fn accelerated_replay() {
+        let mut recording = Recording::default();
+        recording.push(Duration::ZERO, Event::FocusGained);
+        recording.push(Duration::from_secs(60), Event::FocusLost);
+
+        // at 1000x the minute passes in 60ms
+        let inner = MockTerminal::new(Size::new(10, 2)).quit_when_empty(false);
+        let mut replay = Replay::new(recording.clone(), inner).speed(1000.0);
+        assert_eq!(replay.try_read_event(), Some(Event::FocusGained));
+        assert_eq!(replay.try_read_event(), None);
+        assert!(!replay.is_finished());
+
+        std::thread::sleep(Duration::from_millis(70));
+        assert_eq!(replay.try_read_event(), Some(Event::FocusLost));
+        assert!(replay.is_finished());
+        assert_eq!(replay.try_read_event(), None);
+        assert_eq!(replay.try_read_event(), Some(Event::Quit));
+
+        let inner = MockTerminal::new(Size::new(10, 2)).quit_when_empty(false);
+        let mut replay = Replay::new(recording, inner).speed(f32::INFINITY);
+        assert_eq!(replay.try_read_event(), Some(Event::FocusGained));
+        assert_eq!(replay.try_read_event(), Some(Event::FocusLost));
+
+        // without a resize in the recording, the size is the inner backend's
+        assert_eq!(replay.size(), Size::new(10, 2));
+    }
+}
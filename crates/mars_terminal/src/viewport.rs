@@ -0,0 +1,78 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicU32, Ordering},
+};
+
+use mars_math::{Position, Size};
+
+/// The part of the screen the application draws to
+///
+/// This is shared with the event thread, which moves an inline viewport when the terminal is resized
+#[derive(Clone, Debug)]
+pub(crate) struct Viewport {
+    top: Arc<AtomicU32>,
+    // `None` covers the whole screen
+    height: Option<u32>,
+}
+
+impl Viewport {
+    pub(crate) fn fullscreen() -> Self {
+        Self {
+            top: Arc::default(),
+            height: None,
+        }
+    }
+
+    pub(crate) fn inline(top: u32, height: u32) -> Self {
+        Self {
+            top: Arc::new(AtomicU32::new(top)),
+            height: Some(height),
+        }
+    }
+
+    /// The height of an inline viewport
+    pub(crate) const fn height(&self) -> Option<u32> {
+        self.height
+    }
+
+    /// Moves an inline viewport so it ends on this row
+    pub(crate) fn set_bottom(&self, bottom: u32) {
+        if let Some(height) = self.height {
+            let top = (bottom + 1).saturating_sub(height);
+            self.top.store(top, Ordering::Relaxed);
+        }
+    }
+
+    /// Moves an inline viewport so it starts on this row
+    pub(crate) fn set_top(&self, top: u32) {
+        if self.height.is_some() {
+            self.top.store(top, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn origin(&self) -> Position {
+        Position::new(0, self.top.load(Ordering::Relaxed) as i32)
+    }
+
+    /// Fits the viewport onto a screen of this size, returning the size of the viewport
+    pub(crate) fn fit(&self, screen: Size) -> Size {
+        let Some(height) = self.height else {
+            return screen;
+        };
+
+        // keep the bottom of the viewport on the screen
+        let height = height.min(screen.height);
+        let top = self.top.load(Ordering::Relaxed);
+        self.top.store(
+            top.min(screen.height.saturating_sub(height)),
+            Ordering::Relaxed,
+        );
+        Size::new(screen.width, height)
+    }
+
+    /// The last row of an inline viewport
+    pub(crate) fn last_row(&self) -> Option<u32> {
+        let top = self.top.load(Ordering::Relaxed);
+        self.height.map(|height| top + height.saturating_sub(1))
+    }
+}